@@ -0,0 +1,100 @@
+#!/bin/bash
+# Checks gmux's EWMH handling against real clients on a headless X server.
+# Needs Xvfb, xdotool, wmctrl, xterm and python3 with python-xlib, none of
+# which `cargo test` can count on, so this is run by hand:
+#
+#   ./ewmh-test.sh
+set -e
+
+cargo build
+
+DPY=:7
+Xvfb $DPY -screen 0 1280x800x24 &
+XVFB_PID=$!
+sleep 1
+export DISPLAY=$DPY
+
+./target/debug/gmux &
+GMUX_PID=$!
+trap 'kill $GMUX_PID $XVFB_PID 2>/dev/null' EXIT
+sleep 1
+
+FAILED=0
+function check() {
+    if [ "$2" == "$3" ]; then
+        echo "ok   $1"
+    else
+        echo "FAIL $1: expected '$3', got '$2'"
+        FAILED=1
+    fi
+}
+
+# Sends _NET_WM_MOVERESIZE for window $1 with direction $2, as a client would
+function moveresize() {
+    python3 - "$1" "$2" <<'EOF'
+import sys
+from Xlib import X, display, protocol
+d = display.Display()
+root = d.screen().root
+p = root.query_pointer()
+ev = protocol.event.ClientMessage(
+    window=int(sys.argv[1], 0), client_type=d.intern_atom("_NET_WM_MOVERESIZE"),
+    data=(32, [p.root_x, p.root_y, int(sys.argv[2]), 1, 1]))
+root.send_event(ev, event_mask=X.SubstructureRedirectMask | X.SubstructureNotifyMask)
+d.flush()
+EOF
+    sleep 0.3
+}
+
+# Whether another client can grab the pointer, i.e. gmux is not stuck in a drag
+function pointer_free() {
+    python3 - <<'EOF'
+from Xlib import X, display
+d = display.Display()
+status = d.screen().root.grab_pointer(False, X.ButtonPressMask, X.GrabModeAsync, X.GrabModeAsync, X.NONE, X.NONE, X.CurrentTime)
+d.ungrab_pointer(X.CurrentTime)
+d.flush()
+print("free" if status == X.GrabSuccess else "grabbed")
+EOF
+}
+
+check "wm name" "$(wmctrl -m | sed -n 's/^Name: //p')" "dwm"
+
+xterm -title ewmh-test &
+sleep 1
+WIN=$(xdotool search --name ewmh-test | head -n1)
+check "client listed" "$(wmctrl -l | grep -c ewmh-test)" "1"
+
+# Drag with the button held on the bar, so gmux gets the implicit grab
+xdotool mousemove 640 5 mousedown 1
+moveresize "$WIN" 8
+xdotool mousemove 740 105
+sleep 0.3
+xdotool mouseup 1
+sleep 0.3
+eval "$(xdotool getwindowgeometry --shell "$WIN")"
+BEFORE_X=$X
+check "drag ended on release" "$(pointer_free)" "free"
+
+# With no button down the request has to be ignored instead of grabbing
+moveresize "$WIN" 8
+check "no drag without a button" "$(pointer_free)" "free"
+
+# A cancel ends a drag while the button is still down
+xdotool mousemove 640 5 mousedown 1
+moveresize "$WIN" 8
+moveresize "$WIN" 11
+check "cancel ends drag" "$(pointer_free)" "free"
+xdotool mouseup 1
+
+# _NET_MOVERESIZE_WINDOW on the now floating window
+wmctrl -i -r "$WIN" -e "0,$((BEFORE_X + 50)),200,400,300"
+sleep 0.3
+eval "$(xdotool getwindowgeometry --shell "$WIN")"
+check "moveresize window" "$([ "$X" -gt "$BEFORE_X" ] && echo moved)" "moved"
+
+wmctrl -i -c "$WIN"
+sleep 0.5
+check "closed" "$(wmctrl -l | grep -c ewmh-test || true)" "0"
+
+exit $FAILED
@@ -1,3 +1,5 @@
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 use x11::xlib;
 use crate::*;
 
@@ -5,25 +7,43 @@ use crate::*;
 pub enum Action {
     Spawn(String),
     SpawnDirect(String, Vec<String>),
+    SpawnTerminalCwd,
     ToggleBar,
     FocusStack(i32),
+    FocusLast,
+    FocusUrgent,
+    FocusDir(Direction),
+    SwapDir(Direction),
+    MruCycle(i32),
     IncNMaster(i32),
     SetMFact(f32),
+    SetCFact(f32),
+    ResetCFact,
+    IncGaps(i32),
+    ToggleGaps,
+    DefaultGaps,
     Zoom,
+    PushStack(i32),
+    RotateStack(i32),
     ViewTag(u32, Option<usize>),
     ViewPrevTag,
     KillClient,
-    SetLayout(&'static Layout),
+    SetLayout(&'static str),
+    Tree(TreeCommand),
     ToggleFloating,
+    ToggleSticky,
     Tag(u32),
     FocusMon(i32),
     TagMon(i32),
     Quit,
+    Restart,
     ToggleView(u32),
     ToggleTag(u32),
     CycleTag(i32),
     FocusClient(ClientHandle),
+    ToggleScratch(usize),
     EnterLauncherMode,
+    EnterWindowSwitcher,
     TestPanic,
 }
 
@@ -34,15 +54,24 @@ impl Action {
                 state.spawn(cmd);
             }
             Action::SpawnDirect(program, args) => {
-                state.spawn_direct(program, args);
+                state.spawn_direct(program, args, None);
+            }
+            Action::SpawnTerminalCwd => {
+                let cwd = state.selected_cwd()
+                    .or_else(|| std::env::var_os("HOME").map(PathBuf::from));
+                state.spawn_direct(TERMINAL, &[], cwd.as_deref());
             }
             Action::EnterLauncherMode => {
                 state.enter_launcher_mode();
             }
+            Action::EnterWindowSwitcher => {
+                state.enter_window_switcher();
+            }
             Action::ToggleBar => {
                 let selmon_idx = state.selected_monitor;
                 let selmon = &mut state.mons[selmon_idx];
                 selmon.show_bar = !selmon.show_bar;
+                state.update_work_area(selmon_idx);
                 state.arrange(Some(selmon_idx));
             }
             Action::FocusStack(i) => {
@@ -71,6 +100,33 @@ impl Action {
                 }
                 state.restack(selmon_idx);
             }
+            Action::FocusLast => {
+                let selmon = &state.mons[state.selected_monitor];
+                let last = selmon.focus_history.iter()
+                    .find(|h| Some(**h) != selmon.sel && state.clients.contains_key(h))
+                    .cloned();
+                if let Some(handle) = last {
+                    state.activate_client(handle);
+                }
+            }
+            Action::FocusUrgent => {
+                let urgent = state.clients.values()
+                    .filter(|c| c.is_urgent)
+                    .max_by_key(|c| c.urgent_since)
+                    .map(|c| c.handle());
+                if let Some(handle) = urgent {
+                    state.activate_client(handle);
+                }
+            }
+            Action::FocusDir(dir) => {
+                state.focus_dir(*dir);
+            }
+            Action::SwapDir(dir) => {
+                state.swap_dir(*dir);
+            }
+            Action::MruCycle(dir) => {
+                state.start_mru_cycle(*dir);
+            }
             Action::IncNMaster(i) => {
                 let selmon_idx = state.selected_monitor;
                 let selmon = &mut state.mons[selmon_idx];
@@ -80,7 +136,7 @@ impl Action {
             Action::SetMFact(f) => {
                 let selmon_idx = state.selected_monitor;
                 let selmon = &mut state.mons[selmon_idx];
-                if selmon.lt[selmon.selected_lt as usize].arrange.is_none() {
+                if selmon.layout().is_floating() {
                     return;
                 }
                 let new_f = if *f < 1.0 {
@@ -94,27 +150,81 @@ impl Action {
                 selmon.mfact = new_f;
                 state.arrange(Some(selmon_idx));
             }
-            Action::Zoom => {
+            Action::SetCFact(f) => {
                 let selmon_idx = state.selected_monitor;
                 if let Some(sel_handle) = state.mons[selmon_idx].sel {
-                    if let Some(c) = state.clients.get(&sel_handle) {
-                        if state.mons[selmon_idx].lt[state.mons[selmon_idx].selected_lt as usize]
-                            .arrange
-                            .is_none()
-                            || c.is_floating
-                        {
+                    if let Some(client) = state.clients.get_mut(&sel_handle) {
+                        let new_f = client.cfact + *f;
+                        if !(0.25..=4.0).contains(&new_f) {
                             return;
                         }
-
-                        let mon = &mut state.mons[selmon_idx];
-                        if let Some(pos) = mon.stack.iter().position(|h| *h == sel_handle) {
-                            mon.stack.remove(pos);
-                            mon.stack.insert(0, sel_handle);
-                            state.arrange(Some(selmon_idx));
-                        }
+                        client.cfact = new_f;
+                        log::info!("cfact of '{}' set to {:.2}", client.name, client.cfact);
                     }
+                    state.arrange(Some(selmon_idx));
                 }
             }
+            Action::ResetCFact => {
+                let selmon_idx = state.selected_monitor;
+                if let Some(sel_handle) = state.mons[selmon_idx].sel {
+                    if let Some(client) = state.clients.get_mut(&sel_handle) {
+                        client.cfact = 1.0;
+                        log::info!("cfact of '{}' reset", client.name);
+                    }
+                    state.arrange(Some(selmon_idx));
+                }
+            }
+            Action::IncGaps(i) => {
+                let selmon_idx = state.selected_monitor;
+                let selmon = &mut state.mons[selmon_idx];
+                selmon.set_gaps(
+                    selmon.gap_ih + i,
+                    selmon.gap_iv + i,
+                    selmon.gap_oh + i,
+                    selmon.gap_ov + i,
+                );
+                state.arrange(Some(selmon_idx));
+            }
+            Action::ToggleGaps => {
+                let selmon_idx = state.selected_monitor;
+                let selmon = &mut state.mons[selmon_idx];
+                selmon.gaps_enabled = !selmon.gaps_enabled;
+                state.arrange(Some(selmon_idx));
+            }
+            Action::DefaultGaps => {
+                let selmon_idx = state.selected_monitor;
+                let selmon = &mut state.mons[selmon_idx];
+                selmon.gaps_enabled = GAPS_ENABLED;
+                selmon.set_gaps(GAP_IH, GAP_IV, GAP_OH, GAP_OV);
+                state.arrange(Some(selmon_idx));
+            }
+            Action::Zoom => {
+                let selmon_idx = state.selected_monitor;
+                if let Some(sel_handle) = state.tiled_sel(selmon_idx) {
+                    let tiled = state.tiled_visible(selmon_idx);
+                    if let Some(master) = state.mons[selmon_idx].zoom(&tiled, sel_handle) {
+                        state.focus(Some(master));
+                        state.arrange(Some(selmon_idx));
+                    }
+                }
+            }
+            Action::PushStack(dir) => {
+                let selmon_idx = state.selected_monitor;
+                if let Some(sel_handle) = state.tiled_sel(selmon_idx) {
+                    let tiled = state.tiled_visible(selmon_idx);
+                    state.mons[selmon_idx].push(&tiled, sel_handle, *dir);
+                    state.arrange(Some(selmon_idx));
+                }
+            }
+            Action::RotateStack(dir) => {
+                let selmon_idx = state.selected_monitor;
+                if state.mons[selmon_idx].layout().is_floating() {
+                    return;
+                }
+                let tiled = state.tiled_visible(selmon_idx);
+                state.mons[selmon_idx].rotate(&tiled, *dir);
+                state.arrange(Some(selmon_idx));
+            }
             Action::ViewTag(ui, opt_mon_idx) => {
                 let mon_idx = match opt_mon_idx {
                     Some(idx) => {
@@ -140,35 +250,39 @@ impl Action {
                 state.arrange(Some(state.selected_monitor));
             }
             Action::KillClient => {
-                let selmon_idx = state.selected_monitor;
-                if let Some(sel_handle) = state.mons[selmon_idx].sel {
-                    if let Some(client_to_kill) = state.clients.get(&sel_handle) {
-                        if !state.xwrapper.send_wm_protocol_event(
-                            client_to_kill.win,
-                            state.xwrapper.atoms.get(crate::xwrapper::Atom::Wm(crate::xwrapper::WM::Delete)),
-                        ) {
-                            state.xwrapper.grab_server();
-                            state.xwrapper.set_ignore_error_handler();
-                            state.xwrapper.set_close_down_mode(xlib::DestroyAll);
-                            state.xwrapper.kill_client(client_to_kill.win);
-                            state.xwrapper.sync(false);
-                            state.xwrapper.set_default_error_handler();
-                            state.xwrapper.ungrab_server();
-                        }
-                    }
+                if let Some(sel_handle) = state.mons[state.selected_monitor].sel {
+                    state.kill_client(sel_handle);
                 }
             }
-            Action::SetLayout(l) => {
+            Action::SetLayout(name) => {
                 let selmon_idx = state.selected_monitor;
-                let sellt = state.mons[selmon_idx].selected_lt as usize;
-                state.mons[selmon_idx].lt[sellt] = l;
                 let selmon = &mut state.mons[selmon_idx];
-                let symbol = selmon.lt[selmon.selected_lt as usize].symbol;
-                selmon.lt_symbol = symbol.to_string();
+                if !selmon.set_layout(name) {
+                    log::warn!("Unknown layout '{}'", name);
+                    return;
+                }
                 if selmon.sel.is_some() {
                     state.arrange(Some(selmon_idx));
                 }
             }
+            Action::Tree(cmd) => {
+                let selmon_idx = state.selected_monitor;
+                let mon = &mut state.mons[selmon_idx];
+                let sel_handle = match mon.sel {
+                    Some(h) => h,
+                    None => return,
+                };
+                let tagset = mon.tagset[mon.selected_tags as usize] & TAG_MASK;
+                let sellt = mon.selected_lt as usize;
+                let tree = match mon.lt[sellt].as_tree() {
+                    Some(t) => t,
+                    None => return,
+                };
+                if let Some(target) = tree.command(tagset, sel_handle, *cmd) {
+                    state.focus(Some(target));
+                }
+                state.arrange(Some(selmon_idx));
+            }
             Action::ToggleFloating => {
                 let selmon_idx = state.selected_monitor;
                 if let Some(sel_handle) = state.mons[selmon_idx].sel {
@@ -178,6 +292,12 @@ impl Action {
                     state.arrange(Some(selmon_idx));
                 }
             }
+            Action::ToggleSticky => {
+                if let Some(sel_handle) = state.mons[state.selected_monitor].sel {
+                    let sticky = state.clients.get(&sel_handle).is_some_and(|c| c.is_sticky);
+                    state.set_sticky(sel_handle, !sticky);
+                }
+            }
             Action::Tag(ui) => {
                 let selmon_idx = state.selected_monitor;
                 if let Some(sel_handle) = state.mons[selmon_idx].sel {
@@ -220,13 +340,34 @@ impl Action {
                 }
             }
             Action::Quit => {
+                // Quitting again while waiting does not wait any longer
+                if !CLOSE_CLIENTS_ON_QUIT || state.quit_deadline.is_some() {
+                    state.running = 0;
+                    return;
+                }
+                let delete = state.xwrapper.atoms.get(crate::xwrapper::Atom::Wm(crate::xwrapper::WM::Delete));
+                let asked = state.clients.values()
+                    .filter(|c| state.xwrapper.send_wm_protocol_event(c.win, delete))
+                    .count();
+                if asked == 0 {
+                    state.running = 0;
+                } else {
+                    state.quit_deadline = Some(Instant::now() + QUIT_TIMEOUT);
+                }
+            }
+            Action::Restart => {
+                if let Err(e) = state.save_restart_state() {
+                    state.set_error_state(format!("Failed to save state for restart: {}", e));
+                    return;
+                }
+                state.restarting = true;
                 state.running = 0;
             }
             Action::ToggleView(ui) => {
                 let selmon = &mut state.mons[state.selected_monitor];
                 let newtags = selmon.tagset[selmon.selected_tags as usize] ^ (*ui & TAG_MASK);
 
-                if newtags != 0 {
+                if newtags & TAG_MASK != 0 {
                     selmon.tagset[selmon.selected_tags as usize] = newtags;
                     state.arrange(Some(state.selected_monitor));
                 }
@@ -246,7 +387,7 @@ impl Action {
             Action::CycleTag(direction) => {
                 let selmon_idx = state.selected_monitor;
                 let mon = &mut state.mons[selmon_idx];
-                let tagset = mon.tagset[mon.selected_tags as usize];
+                let tagset = mon.tagset[mon.selected_tags as usize] & TAG_MASK;
 
                 let num_tags = config::TAGS.len() as i32;
                 let new_tag_idx = if tagset == 0 {
@@ -269,6 +410,9 @@ impl Action {
                 mon.tagset[mon.selected_tags as usize] = 1 << new_tag_idx;
                 state.arrange(Some(selmon_idx));
             }
+            Action::ToggleScratch(i) => {
+                state.toggle_scratch(*i);
+            }
             Action::FocusClient(handle) => {
                 state.focus(Some(*handle));
                 state.restack(state.selected_monitor);
@@ -283,6 +427,225 @@ impl Action {
 }
 
 impl Gmux {
+    fn client_rect(&self, handle: ClientHandle) -> Option<Rect> {
+        self.clients.get(&handle).map(|c| Rect::new(c.x, c.y, c.width(), c.height()))
+    }
+
+    /// The visible tiled clients of a monitor, in stack order.
+    pub fn tiled_visible(&self, mon_idx: usize) -> Vec<ClientHandle> {
+        let mon = &self.mons[mon_idx];
+        mon.stack.iter()
+            .filter(|h| self.clients.get(h).is_some_and(|c| c.is_visible_on(mon) && !c.is_floating))
+            .copied()
+            .collect()
+    }
+
+    /// The selected client, if it is tiled and the layout tiles at all.
+    fn tiled_sel(&self, mon_idx: usize) -> Option<ClientHandle> {
+        let mon = &self.mons[mon_idx];
+        if mon.layout().is_floating() {
+            return None;
+        }
+        mon.sel.filter(|h| self.clients.get(h).is_some_and(|c| !c.is_floating))
+    }
+
+    /// Visible clients of a monitor with their on-screen rectangles.
+    fn visible_rects(&self, mon_idx: usize, tiled_only: bool) -> Vec<(ClientHandle, Rect)> {
+        let mon = &self.mons[mon_idx];
+        mon.stack.iter()
+            .filter(|h| self.clients.get(h).is_some_and(|c| c.is_visible_on(mon) && !(tiled_only && c.is_floating)))
+            .filter_map(|&h| Some((h, self.client_rect(h)?)))
+            .collect()
+    }
+
+    /// The monitor next to `from` in a direction, if any.
+    fn monitor_in_direction(&self, from: usize, dir: Direction) -> Option<usize> {
+        let rect = |m: &Monitor| Rect::new(m.wx, m.wy, m.ww, m.wh);
+        let others: Vec<(usize, Rect)> = self.mons.iter().enumerate()
+            .filter(|(i, _)| *i != from)
+            .map(|(i, m)| (i, rect(m)))
+            .collect();
+        nearest_in_direction(rect(&self.mons[from]), &others, dir)
+    }
+
+    /// Focuses the nearest visible client in a direction. At the edge of a monitor
+    /// focus moves on to the closest client of the monitor beyond it.
+    pub fn focus_dir(&mut self, dir: Direction) {
+        let selmon_idx = self.selected_monitor;
+        let sel = self.mons[selmon_idx].sel;
+        let from = match sel.and_then(|h| self.client_rect(h)) {
+            Some(r) => r,
+            None => {
+                let m = &self.mons[selmon_idx];
+                Rect::new(m.wx, m.wy, m.ww, m.wh)
+            }
+        };
+
+        let candidates: Vec<_> = self.visible_rects(selmon_idx, false).into_iter()
+            .filter(|(h, _)| Some(*h) != sel)
+            .collect();
+        if let Some(target) = nearest_in_direction(from, &candidates, dir) {
+            self.focus(Some(target));
+            self.restack(selmon_idx);
+            return;
+        }
+
+        let mon_idx = match self.monitor_in_direction(selmon_idx, dir) {
+            Some(i) => i,
+            None => return,
+        };
+        let candidates = self.visible_rects(mon_idx, false);
+        let target = nearest_in_direction(from, &candidates, dir).or(self.mons[mon_idx].sel);
+        if let Some(h) = sel {
+            self.unfocus(h, false);
+        }
+        self.selected_monitor = mon_idx;
+        self.focus(target);
+        self.restack(mon_idx);
+    }
+
+    /// Swaps the selected tiled client with its nearest tiled neighbour in a
+    /// direction. At the edge of a monitor the client moves to the monitor beyond.
+    pub fn swap_dir(&mut self, dir: Direction) {
+        let selmon_idx = self.selected_monitor;
+        let sel = match self.mons[selmon_idx].sel {
+            Some(h) if self.clients.get(&h).is_some_and(|c| !c.is_floating) => h,
+            _ => return,
+        };
+        if self.mons[selmon_idx].layout().is_floating() {
+            return;
+        }
+
+        // The tree layout keeps its own order
+        let mon = &mut self.mons[selmon_idx];
+        let tagset = mon.tagset[mon.selected_tags as usize] & TAG_MASK;
+        let sellt = mon.selected_lt as usize;
+        if let Some(tree) = mon.lt[sellt].as_tree() {
+            tree.command(tagset, sel, TreeCommand::Move(dir));
+            self.arrange(Some(selmon_idx));
+            return;
+        }
+
+        let from = match self.client_rect(sel) {
+            Some(r) => r,
+            None => return,
+        };
+        let candidates: Vec<_> = self.visible_rects(selmon_idx, true).into_iter()
+            .filter(|(h, _)| *h != sel)
+            .collect();
+        if let Some(target) = nearest_in_direction(from, &candidates, dir) {
+            let stack = &mut self.mons[selmon_idx].stack;
+            let a = stack.iter().position(|h| *h == sel);
+            let b = stack.iter().position(|h| *h == target);
+            if let (Some(a), Some(b)) = (a, b) {
+                stack.swap(a, b);
+                self.arrange(Some(selmon_idx));
+            }
+            return;
+        }
+
+        if let Some(mon_idx) = self.monitor_in_direction(selmon_idx, dir) {
+            self.send_to_monitor(sel, mon_idx);
+        }
+    }
+
+    /// Moves a client to another monitor, onto that monitor's current tags, and
+    /// keeps it focused there.
+    pub fn send_to_monitor(&mut self, handle: ClientHandle, mon_idx: usize) {
+        let old_idx = match self.clients.get(&handle) {
+            Some(c) if c.monitor_idx != mon_idx => c.monitor_idx,
+            _ => return,
+        };
+        self.unfocus(handle, false);
+        let old_mon = &mut self.mons[old_idx];
+        old_mon.stack.retain(|&h| h != handle);
+        old_mon.forget(handle);
+        if old_mon.sel == Some(handle) {
+            old_mon.sel = None;
+        }
+
+        let tiled = self.tiled_visible(mon_idx);
+        let mon = &mut self.mons[mon_idx];
+        let tags = mon.tagset[mon.selected_tags as usize] & TAG_MASK;
+        mon.attach(handle, mon.attach, &tiled);
+        if let Some(c) = self.clients.get_mut(&handle) {
+            c.monitor_idx = mon_idx;
+            c.tags = tags;
+        }
+        self.arrange(Some(old_idx));
+        self.selected_monitor = mon_idx;
+        self.focus(Some(handle));
+        self.arrange(Some(mon_idx));
+    }
+
+    /// Focuses a client, first switching its monitor to the client's tags if it
+    /// is not visible.
+    pub fn activate_client(&mut self, handle: ClientHandle) {
+        self.set_hidden(handle, false);
+        let (mon_idx, tags) = match self.clients.get(&handle) {
+            Some(c) if !c.is_visible_on(&self.mons[c.monitor_idx]) => (c.monitor_idx, c.tags & TAG_MASK),
+            Some(c) => (c.monitor_idx, 0),
+            None => return,
+        };
+        if tags != 0 {
+            let mon = &mut self.mons[mon_idx];
+            mon.selected_tags ^= 1;
+            mon.tagset[mon.selected_tags as usize] = tags;
+        }
+        self.focus(Some(handle));
+        self.arrange(Some(mon_idx));
+    }
+
+    /// Shows or hides scratchpad `i` on the selected monitor, spawning it first if
+    /// it is not running. A scratchpad on another monitor is brought over.
+    pub fn toggle_scratch(&mut self, i: usize) {
+        let sp = match SCRATCHPADS.get(i) {
+            Some(sp) => sp,
+            None => return,
+        };
+        let tag = scratch_tag(i);
+        let selmon_idx = self.selected_monitor;
+        let found = self.clients.values()
+            .find(|c| c.tags & tag != 0)
+            .map(|c| (c.handle(), c.monitor_idx));
+
+        let (handle, mon_idx) = match found {
+            Some(f) => f,
+            None => {
+                // `manage` shows the new window once the rule gives it the tag
+                self.spawn(sp.cmd);
+                return;
+            }
+        };
+
+        if mon_idx != selmon_idx {
+            let old_mon = &mut self.mons[mon_idx];
+            old_mon.stack.retain(|&h| h != handle);
+            old_mon.forget(handle);
+            if old_mon.sel == Some(handle) {
+                old_mon.sel = None;
+            }
+            old_mon.tagset[old_mon.selected_tags as usize] &= !tag;
+            self.mons[selmon_idx].stack.insert(0, handle);
+            if let Some(c) = self.clients.get_mut(&handle) {
+                c.monitor_idx = selmon_idx;
+                c.centre_on(&self.mons[selmon_idx], sp);
+            }
+            let mon = &mut self.mons[selmon_idx];
+            mon.tagset[mon.selected_tags as usize] |= tag;
+            self.arrange(Some(mon_idx));
+        } else {
+            let mon = &mut self.mons[selmon_idx];
+            mon.tagset[mon.selected_tags as usize] ^= tag;
+        }
+
+        let mon = &self.mons[selmon_idx];
+        if mon.tagset[mon.selected_tags as usize] & tag != 0 {
+            self.focus(Some(handle));
+        }
+        self.arrange(Some(selmon_idx));
+    }
+
     pub fn spawn(&mut self, cmd: &str) {
         let sender = self.command_sender.clone();
         let command_string = cmd.to_string();
@@ -323,17 +686,31 @@ impl Gmux {
         });
     }
 
+    /// Working directory of the shell inside the selected client, if it is a terminal.
+    fn selected_cwd(&self) -> Option<PathBuf> {
+        let client = self.mons[self.selected_monitor].get_sel_client(&self.clients)?;
+        if client.pid == 0 {
+            return None;
+        }
+        let shell = ProcFs.deepest_shell(client.pid)?;
+        ProcFs.cwd(shell)
+    }
+
     /// Spawns a command directly in a new thread to collect errors without blocking.
-    pub fn spawn_direct(&mut self, program: &str, args: &[String]) {
+    pub fn spawn_direct(&mut self, program: &str, args: &[String], cwd: Option<&Path>) {
         let sender = self.command_sender.clone();
         let program_string = program.to_string();
         let args_vec: Vec<String> = args.to_vec();
         let full_command_str = format!("{} {}", program_string, args_vec.join(" "));
+        let cwd = cwd.map(Path::to_path_buf);
 
         thread::spawn(move || {
-            let output_result = Command::new(&program_string)
-                .args(&args_vec)
-                .output(); // This blocks the new thread, NOT the main event loop.
+            let mut command = Command::new(&program_string);
+            command.args(&args_vec);
+            if let Some(dir) = &cwd {
+                command.current_dir(dir);
+            }
+            let output_result = command.output(); // This blocks the new thread, NOT the main event loop.
 
             let output = match output_result {
                 Ok(o) => o,
@@ -10,6 +10,7 @@ pub enum BarState {
         expiry: Instant,
     },
     Launcher {
+        mode: LauncherMode,
         prompt: String,
         input: String,
         candidate_indices: Vec<usize>,
@@ -106,15 +107,27 @@ impl Gmux {
             pos.x += w as i32;
         }
 
+        // Layout symbol
+        let lt_symbol = self.mons[mon_idx].lt_symbol.clone();
+        let w = self.get_text_width(&lt_symbol);
+        let lt_wh = ivec2(w as _, self.bar_height);
+        self.xwrapper.rect(Colour::BarBackground, pos, lt_wh, true);
+        self.xwrapper.text(Colour::TextNormal, pos, lt_wh, self.lr_padding / 2, &lt_symbol);
+        pos.x += w as i32;
+
         // --- 3. Render Right-aligned elements (Status Text) ---
-        let s = Local::now().format("%B %d %H:%M").to_string();
+        // A window that is slow to close takes the place of the clock
+        let (status_col, s) = match self.close_notice(mon_idx) {
+            Some(notice) => (Colour::TextNormal, notice),
+            None => (Colour::TextQuiet, Local::now().format("%B %d %H:%M").to_string()),
+        };
         // --- MODIFIED: Use the new helper function ---
         let w_right = self.get_text_width(&s);
         let p_right = ivec2(bar_wh.x - w_right as i32, 0);
         let wh_right = ivec2(w_right as i32, self.bar_height);
         self.xwrapper.rect(Colour::BarBackground, p_right, wh_right, true);
         // --- MODIFIED: Use lr_padding/2 for the text offset ---
-        self.xwrapper.text(Colour::TextQuiet, p_right, wh_right, self.lr_padding / 2, &s);
+        self.xwrapper.text(status_col, p_right, wh_right, self.lr_padding / 2, &s);
 
         // --- 4. Render Centered elements (Window Title) ---
         let wh_center = (bar_wh - pos) - wh_right.proj_x();
@@ -130,6 +143,11 @@ impl Gmux {
         self.xwrapper.rect(col, pos, wh_center, true);
         // --- MODIFIED: Use lr_padding/2 for the text offset ---
         self.xwrapper.text(Colour::TextNormal, pos, wh_center, self.lr_padding / 2, &text_to_draw);
+        // Sticky windows get a filled box in front of their title
+        let sticky = self.mons[mon_idx].get_sel_client(&self.clients).is_some_and(|c| c.is_sticky);
+        if sticky {
+            self.xwrapper.rect(Colour::TextNormal, pos + box_xy, box_wh, true);
+        }
 
         // --- 5. Map the drawing buffer to the screen ---
         self.xwrapper.map_drawable(barwin, 0, 0, bar_wh.x as u32, bar_wh.y as u32);
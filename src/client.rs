@@ -37,14 +37,32 @@ pub struct Client {
     pub min_width: i32,
     pub min_height: i32,
     pub bw: i32,
+    /// Relative height of the client within its column in tiled layouts.
+    pub cfact: f32,
     pub _oldbw: i32,
+    /// Border width the window had before it was managed, given back on unmanage.
+    pub orig_bw: i32,
     pub tags: u32,
     pub is_fixed: bool,
     pub is_floating: bool,
     pub is_urgent: bool,
-    pub _never_focus: bool,
+    /// When the client last became urgent.
+    pub urgent_since: Option<std::time::Instant>,
+    pub never_focus: bool,
     pub _old_state: bool,
     pub is_fullscreen: bool,
+    /// Shown on every tag of its monitor.
+    pub is_sticky: bool,
+    /// Kept above other windows.
+    pub is_above: bool,
+    /// Iconified: parked off-screen and out of the layout until activated.
+    /// Windows adopted as iconic by `scan` stay unmapped until then.
+    pub is_hidden: bool,
+    /// _NET_WM_PID of the client, 0 if unknown.
+    pub pid: u32,
+    /// Programs started from this terminal swallow it.
+    pub is_terminal: bool,
+    pub no_swallow: bool,
     pub monitor_idx: usize,
     pub win: Window,
 }
@@ -58,7 +76,30 @@ impl Client {
         self.w + 2 * self.bw
     }
 
+    pub fn height(&self) -> i32 {
+        self.h + 2 * self.bw
+    }
+
+    /// Sizes a scratchpad window to its configured share of the monitor's window
+    /// area and centres it there.
+    pub fn centre_on(&mut self, m: &Monitor, sp: &Scratchpad) {
+        self.w = (m.ww as f32 * sp.width) as i32 - 2 * self.bw;
+        self.h = (m.wh as f32 * sp.height) as i32 - 2 * self.bw;
+        self.x = m.wx + (m.ww - self.width()) / 2;
+        self.y = m.wy + (m.wh - self.height()) / 2;
+    }
+
+    /// The _NET_WM_DESKTOP of the client: its first tag, or 0xFFFFFFFF (all
+    /// desktops) when it is sticky or only on a scratchpad tag.
+    pub fn desktop(&self) -> u32 {
+        match self.tags & TAG_MASK {
+            0 => u32::MAX,
+            _ if self.is_sticky => u32::MAX,
+            tags => tags.trailing_zeros(),
+        }
+    }
+
     pub fn is_visible_on(&self, m: &Monitor) -> bool {
-        (self.tags & m.tagset[m.selected_tags as usize]) != 0
+        !self.is_hidden && (self.is_sticky || (self.tags & m.tagset[m.selected_tags as usize]) != 0)
     }
 }
\ No newline at end of file
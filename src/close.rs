@@ -0,0 +1,126 @@
+use std::time::Instant;
+
+use x11::xlib;
+
+use crate::*;
+
+/// A window that was asked to close with WM_DELETE_WINDOW.
+#[derive(Debug, Clone)]
+pub struct PendingClose {
+    pub deadline: Instant,
+    /// The deadline passed and killing the window is on offer until a second
+    /// `CLOSE_TIMEOUT` has passed.
+    pub expired: bool,
+}
+
+impl Gmux {
+    /// Asks a client to close with WM_DELETE_WINDOW, or kills it if it does not
+    /// support that. Closing it again after `CLOSE_TIMEOUT` kills it.
+    pub fn kill_client(&mut self, handle: ClientHandle) {
+        let win = match self.clients.get(&handle) {
+            Some(c) => c.win,
+            None => return,
+        };
+        if let Some(pending) = self.pending_close.get(&handle) {
+            if pending.expired {
+                self.force_kill(handle);
+            }
+            return;
+        }
+        let delete = self.xwrapper.atoms.get(Atom::Wm(WM::Delete));
+        if !self.xwrapper.send_wm_protocol_event(win, delete) {
+            self.x_kill_client(win);
+            return;
+        }
+        self.pending_close.insert(handle, PendingClose { deadline: Instant::now() + CLOSE_TIMEOUT, expired: false });
+        self.draw_bars();
+    }
+
+    /// Called from the event loop to act on closes that timed out. A window
+    /// that is still there once the offer to kill it runs out, say because
+    /// its "save changes?" dialog was cancelled, is left alone.
+    pub fn check_pending_close(&mut self) {
+        let now = Instant::now();
+        let timed_out: Vec<ClientHandle> = self.pending_close.iter()
+            .filter(|(_, p)| now >= p.deadline)
+            .map(|(&h, _)| h)
+            .collect();
+        if timed_out.is_empty() {
+            return;
+        }
+        for handle in timed_out {
+            let pending = match self.pending_close.get_mut(&handle) {
+                Some(p) => p,
+                None => continue,
+            };
+            if pending.expired {
+                self.pending_close.remove(&handle);
+            } else if FORCE_KILL_ON_TIMEOUT {
+                self.force_kill(handle);
+            } else {
+                pending.expired = true;
+                pending.deadline = now + CLOSE_TIMEOUT;
+            }
+        }
+        self.draw_bars();
+    }
+
+    /// Stops waiting on a client that went away.
+    pub fn forget_pending_close(&mut self, handle: ClientHandle) {
+        if self.pending_close.remove(&handle).is_some() {
+            self.draw_bars();
+        }
+    }
+
+    /// What the bar of a monitor says about its most recent pending close.
+    pub fn close_notice(&self, mon_idx: usize) -> Option<String> {
+        let (c, pending) = self.pending_close.iter()
+            .filter_map(|(h, p)| Some((self.clients.get(h)?, p)))
+            .filter(|(c, _)| c.monitor_idx == mon_idx)
+            .max_by_key(|(_, p)| p.deadline)?;
+        Some(if pending.expired {
+            format!("{} is not responding, close it again to kill it", c.name)
+        } else {
+            format!("Closing {}...", c.name)
+        })
+    }
+
+    /// Kills a client that would not close: its process with SIGKILL when
+    /// `_NET_WM_PID` is known and WM_CLIENT_MACHINE says it runs on this host,
+    /// and its X connection in any case.
+    fn force_kill(&mut self, handle: ClientHandle) {
+        let (win, pid) = match self.clients.get(&handle) {
+            Some(c) => (c.win, c.pid),
+            None => return,
+        };
+        log::warn!("Killing unresponsive client {:?} (pid {})", win, pid);
+        // A pid from another host would name some unrelated local process
+        let local = self.xwrapper.get_client_machine(win).is_some_and(|m| Some(m) == hostname());
+        if pid != 0 && local {
+            unsafe {
+                libc::kill(pid as libc::pid_t, libc::SIGKILL);
+            }
+        }
+        self.x_kill_client(win);
+        self.forget_pending_close(handle);
+    }
+
+    fn x_kill_client(&self, win: Window) {
+        self.xwrapper.grab_server();
+        self.xwrapper.set_ignore_error_handler();
+        self.xwrapper.set_close_down_mode(xlib::DestroyAll);
+        self.xwrapper.kill_client(win);
+        self.xwrapper.sync(false);
+        self.xwrapper.set_default_error_handler();
+        self.xwrapper.ungrab_server();
+    }
+}
+
+fn hostname() -> Option<String> {
+    let mut buf = [0u8; 256];
+    if unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) } != 0 {
+        return None;
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    Some(String::from_utf8_lossy(&buf[..len]).into_owned())
+}
@@ -1,9 +1,43 @@
-use crate::Action;
+use crate::{Action, AttachMode, ContainerMode, Direction, SplitDir, TreeCommand};
 use x11::{keysym, xlib};
 use lazy_static::lazy_static;
 use std::path::PathBuf;
+use std::time::Duration;
 
 pub const BORDER_PX: i32 = 6;
+pub const TERMINAL: &str = "alacritty";
+/// Held while cycling through recently used windows with Tab. The cycle ends and
+/// focuses the chosen window when one of the release keys goes up.
+pub const MRU_MOD: u32 = xlib::Mod4Mask;
+pub const MRU_RELEASE_KEYS: [u32; 2] = [keysym::XK_Super_L, keysym::XK_Super_R];
+/// Drop the border of a client that is the only tiled window on its monitor,
+/// or of every tiled client while the layout is monocle.
+pub const SMART_BORDERS: bool = true;
+/// Where new windows go in the stack. Rules can override this per window.
+pub const ATTACH_MODE: AttachMode = AttachMode::Top;
+/// Ask every window to close on quit, and quit once they have or after
+/// `QUIT_TIMEOUT`. Quitting a second time quits straight away.
+pub const CLOSE_CLIENTS_ON_QUIT: bool = false;
+pub const QUIT_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long a window gets to close after being asked to. Once it has passed,
+/// closing the window again within the same time kills it, or with
+/// `FORCE_KILL_ON_TIMEOUT` it is killed straight away.
+pub const CLOSE_TIMEOUT: Duration = Duration::from_secs(3);
+pub const FORCE_KILL_ON_TIMEOUT: bool = false;
+/// Let floating windows swallow the terminal they were started from.
+pub const SWALLOW_FLOATING: bool = false;
+// Vanity gaps: inner gaps sit between windows, outer gaps between windows and
+// the screen edge. `h` is horizontal, `v` is vertical. They start out off and
+// alt+g turns them on.
+pub const GAPS_ENABLED: bool = false;
+pub const GAP_IH: i32 = 10;
+pub const GAP_IV: i32 = 10;
+pub const GAP_OH: i32 = 10;
+pub const GAP_OV: i32 = 10;
+/// Drop the outer gaps when only one client is tiled.
+pub const SMART_GAPS: bool = false;
+/// Each character is drawn with the first of these fonts that has it. Fontconfig
+/// is asked for a similar font when none of them do.
 pub const FONTS: &[&str] = &["monospace:size=20"];
 
 lazy_static! {
@@ -33,38 +67,87 @@ pub struct Rule {
     pub class: Option<String>,
     pub instance: Option<String>, 
     pub title: Option<String>,
-    pub tags: u32,
+    pub tags: u32, // 0 keeps the current tags
     pub is_floating: bool,
     pub monitor: i32, // -1 for current monitor
+    pub scratchpad: Option<usize>, // index into SCRATCHPADS
+    pub is_terminal: bool,
+    pub no_swallow: bool,
+    pub attach: Option<AttachMode>, // None uses the monitor's attach mode
 }
 
-pub fn rules() -> Vec<Rule> {
-    vec![
+impl Default for Rule {
+    fn default() -> Self {
         Rule {
-            class: Some("firefox".to_string()),
+            class: None,
             instance: None,
             title: None,
-            tags: 1 << 4, // Tag 5
+            tags: 0,
             is_floating: false,
             monitor: -1,
+            scratchpad: None,
+            is_terminal: false,
+            no_swallow: false,
+            attach: None,
+        }
+    }
+}
+
+/// A floating window that is spawned on first use and then toggled in and out of
+/// view on the focused monitor. Its rule matches on the instance name given to
+/// the command. Width and height are fractions of the monitor's window area.
+#[derive(Debug, Clone, Copy)]
+pub struct Scratchpad {
+    pub instance: &'static str,
+    pub cmd: &'static str,
+    pub width: f32,
+    pub height: f32,
+}
+
+pub const SCRATCHPADS: [Scratchpad; 3] = [
+    Scratchpad { instance: "scratchterm", cmd: "alacritty --class scratchterm", width: 0.6, height: 0.6 },
+    Scratchpad { instance: "scratchcalc", cmd: "alacritty --class scratchcalc -e bc -lq", width: 0.3, height: 0.4 },
+    Scratchpad { instance: "scratchnotes", cmd: "alacritty --class scratchnotes -e nvim notes.md", width: 0.5, height: 0.7 },
+];
+
+pub fn rules() -> Vec<Rule> {
+    // Scratchpads go first so their instance names win over the terminal rule
+    let mut rules: Vec<Rule> = SCRATCHPADS.iter().enumerate()
+        .map(|(i, sp)| Rule {
+            instance: Some(sp.instance.to_string()),
+            scratchpad: Some(i),
+            ..Rule::default()
+        })
+        .collect();
+    rules.extend(vec![
+        Rule {
+            class: Some("Alacritty".to_string()),
+            is_terminal: true,
+            attach: Some(AttachMode::Aside),
+            ..Rule::default()
+        },
+        Rule {
+            title: Some("Event Tester".to_string()), // xev
+            no_swallow: true,
+            ..Rule::default()
+        },
+        Rule {
+            class: Some("firefox".to_string()),
+            tags: 1 << 4, // Tag 5
+            ..Rule::default()
         },
         Rule {
             class: Some("discord".to_string()),
-            instance: None,
-            title: None,
             tags: 1 << 3, // Tag 4
-            is_floating: false,
-            monitor: -1,
+            ..Rule::default()
         },
         Rule {
             class: Some("steam".to_string()),
-            instance: None,
-            title: None,
             tags: 1 << 2, // Tag 4
-            is_floating: false,
-            monitor: -1,
+            ..Rule::default()
         },
-    ]
+    ]);
+    rules
 }
 
 pub struct KeyBinding {
@@ -76,6 +159,7 @@ pub struct KeyBinding {
 pub fn grab_keys() -> Vec<KeyBinding> {
     const MOD: u32 = xlib::Mod1Mask;
     const SHIFT_MASK: u32 = xlib::ShiftMask;
+    const SUPER: u32 = xlib::Mod4Mask;
 
     let mut keys: Vec<KeyBinding> = vec![];
     keys.push(KeyBinding {
@@ -86,13 +170,18 @@ pub fn grab_keys() -> Vec<KeyBinding> {
     keys.push(KeyBinding {
         mask: MOD | SHIFT_MASK,
         keysym: keysym::XK_Return,
-        action: Action::SpawnDirect("alacritty".to_string(), vec![]),
+        action: Action::SpawnDirect(TERMINAL.to_string(), vec![]),
+    });
+    keys.push(KeyBinding {
+        mask: xlib::ControlMask | SHIFT_MASK,
+        keysym: keysym::XK_Return,
+        action: Action::SpawnTerminalCwd,
     });
     keys.push(KeyBinding {
         mask: MOD | SHIFT_MASK,
         keysym: keysym::XK_l,
         action: Action::SpawnDirect(
-            "alacritty".to_string(),
+            TERMINAL.to_string(),
             vec![
                 "-e".to_string(),
                 "tail".to_string(),
@@ -101,6 +190,21 @@ pub fn grab_keys() -> Vec<KeyBinding> {
             ],
         ),
     });
+    keys.push(KeyBinding {
+        mask: MOD,
+        keysym: keysym::XK_y,
+        action: Action::ToggleScratch(0),
+    });
+    keys.push(KeyBinding {
+        mask: MOD,
+        keysym: keysym::XK_c,
+        action: Action::ToggleScratch(1),
+    });
+    keys.push(KeyBinding {
+        mask: MOD,
+        keysym: keysym::XK_n,
+        action: Action::ToggleScratch(2),
+    });
     keys.push(KeyBinding {
         mask: MOD,
         keysym: keysym::XK_b,
@@ -116,6 +220,31 @@ pub fn grab_keys() -> Vec<KeyBinding> {
         keysym: keysym::XK_k,
         action: Action::FocusStack(-1),
     });
+    keys.push(KeyBinding {
+        mask: MOD,
+        keysym: keysym::XK_grave,
+        action: Action::FocusLast,
+    });
+    keys.push(KeyBinding {
+        mask: MOD,
+        keysym: keysym::XK_u,
+        action: Action::FocusUrgent,
+    });
+    keys.push(KeyBinding {
+        mask: MRU_MOD,
+        keysym: keysym::XK_Tab,
+        action: Action::MruCycle(1),
+    });
+    keys.push(KeyBinding {
+        mask: MRU_MOD | SHIFT_MASK,
+        keysym: keysym::XK_Tab,
+        action: Action::MruCycle(-1),
+    });
+    keys.push(KeyBinding {
+        mask: MOD | SHIFT_MASK,
+        keysym: keysym::XK_w,
+        action: Action::EnterWindowSwitcher,
+    });
     keys.push(KeyBinding {
         mask: MOD,
         keysym: keysym::XK_i,
@@ -136,11 +265,66 @@ pub fn grab_keys() -> Vec<KeyBinding> {
         keysym: keysym::XK_l,
         action: Action::SetMFact(0.05),
     });
+    keys.push(KeyBinding {
+        mask: MOD | xlib::ControlMask,
+        keysym: keysym::XK_h,
+        action: Action::SetCFact(-0.25),
+    });
+    keys.push(KeyBinding {
+        mask: MOD | xlib::ControlMask,
+        keysym: keysym::XK_l,
+        action: Action::SetCFact(0.25),
+    });
+    keys.push(KeyBinding {
+        mask: MOD | xlib::ControlMask,
+        keysym: keysym::XK_o,
+        action: Action::ResetCFact,
+    });
     keys.push(KeyBinding {
         mask: MOD,
         keysym: keysym::XK_Return,
         action: Action::Zoom,
     });
+    keys.push(KeyBinding {
+        mask: MOD | SHIFT_MASK,
+        keysym: keysym::XK_j,
+        action: Action::PushStack(1),
+    });
+    keys.push(KeyBinding {
+        mask: MOD | SHIFT_MASK,
+        keysym: keysym::XK_k,
+        action: Action::PushStack(-1),
+    });
+    keys.push(KeyBinding {
+        mask: MOD | xlib::ControlMask,
+        keysym: keysym::XK_j,
+        action: Action::RotateStack(1),
+    });
+    keys.push(KeyBinding {
+        mask: MOD | xlib::ControlMask,
+        keysym: keysym::XK_k,
+        action: Action::RotateStack(-1),
+    });
+    keys.push(KeyBinding {
+        mask: MOD,
+        keysym: keysym::XK_equal,
+        action: Action::IncGaps(5),
+    });
+    keys.push(KeyBinding {
+        mask: MOD,
+        keysym: keysym::XK_minus,
+        action: Action::IncGaps(-5),
+    });
+    keys.push(KeyBinding {
+        mask: MOD,
+        keysym: keysym::XK_g,
+        action: Action::ToggleGaps,
+    });
+    keys.push(KeyBinding {
+        mask: MOD | SHIFT_MASK,
+        keysym: keysym::XK_g,
+        action: Action::DefaultGaps,
+    });
     keys.push(KeyBinding {
         mask: MOD | SHIFT_MASK,
         keysym: keysym::XK_c,
@@ -149,13 +333,107 @@ pub fn grab_keys() -> Vec<KeyBinding> {
     keys.push(KeyBinding {
         mask: MOD,
         keysym: keysym::XK_space,
-        action: Action::SetLayout(&crate::layouts::LAYOUTS[0]),
+        action: Action::SetLayout("tile"),
+    });
+    keys.push(KeyBinding {
+        mask: MOD,
+        keysym: keysym::XK_m,
+        action: Action::SetLayout("monocle"),
+    });
+    keys.push(KeyBinding {
+        mask: MOD | SHIFT_MASK,
+        keysym: keysym::XK_d,
+        action: Action::SetLayout("deck"),
+    });
+    keys.push(KeyBinding {
+        mask: MOD,
+        keysym: keysym::XK_r,
+        action: Action::SetLayout("spiral"),
+    });
+    keys.push(KeyBinding {
+        mask: MOD | SHIFT_MASK,
+        keysym: keysym::XK_r,
+        action: Action::SetLayout("dwindle"),
+    });
+    keys.push(KeyBinding {
+        mask: MOD,
+        keysym: keysym::XK_t,
+        action: Action::SetLayout("tree"),
+    });
+    keys.push(KeyBinding {
+        mask: MOD,
+        keysym: keysym::XK_v,
+        action: Action::Tree(TreeCommand::Split(SplitDir::Vertical)),
     });
+    keys.push(KeyBinding {
+        mask: MOD | SHIFT_MASK,
+        keysym: keysym::XK_v,
+        action: Action::Tree(TreeCommand::Split(SplitDir::Horizontal)),
+    });
+    keys.push(KeyBinding {
+        mask: MOD | SHIFT_MASK,
+        keysym: keysym::XK_t,
+        action: Action::Tree(TreeCommand::ToggleMode(ContainerMode::Tabbed)),
+    });
+    keys.push(KeyBinding {
+        mask: MOD | xlib::ControlMask,
+        keysym: keysym::XK_t,
+        action: Action::Tree(TreeCommand::ToggleMode(ContainerMode::Stacked)),
+    });
+    keys.push(KeyBinding {
+        mask: MOD | xlib::ControlMask,
+        keysym: keysym::XK_Left,
+        action: Action::Tree(TreeCommand::Resize(-0.05)),
+    });
+    keys.push(KeyBinding {
+        mask: MOD | xlib::ControlMask,
+        keysym: keysym::XK_Right,
+        action: Action::Tree(TreeCommand::Resize(0.05)),
+    });
+    for &(keysym, dir) in &[
+        (keysym::XK_h, Direction::Left),
+        (keysym::XK_l, Direction::Right),
+        (keysym::XK_k, Direction::Up),
+        (keysym::XK_j, Direction::Down),
+    ] {
+        keys.push(KeyBinding {
+            mask: SUPER,
+            keysym,
+            action: Action::FocusDir(dir),
+        });
+        keys.push(KeyBinding {
+            mask: SUPER | SHIFT_MASK,
+            keysym,
+            action: Action::SwapDir(dir),
+        });
+    }
+    for &(keysym, dir) in &[
+        (keysym::XK_Left, Direction::Left),
+        (keysym::XK_Right, Direction::Right),
+        (keysym::XK_Up, Direction::Up),
+        (keysym::XK_Down, Direction::Down),
+    ] {
+        keys.push(KeyBinding {
+            mask: MOD,
+            keysym,
+            action: Action::Tree(TreeCommand::Focus(dir)),
+        });
+        keys.push(KeyBinding {
+            mask: MOD | SHIFT_MASK,
+            keysym,
+            action: Action::Tree(TreeCommand::Move(dir)),
+        });
+    }
     keys.push(KeyBinding {
         mask: MOD | SHIFT_MASK,
         keysym: keysym::XK_space,
         action: Action::ToggleFloating,
     });
+    keys.push(KeyBinding {
+        mask: MOD | SHIFT_MASK,
+        keysym: keysym::XK_s,
+        action: Action::ToggleSticky,
+    });
     keys.push(KeyBinding {
         mask: MOD,
         keysym: keysym::XK_0,
@@ -191,6 +469,11 @@ pub fn grab_keys() -> Vec<KeyBinding> {
         keysym: keysym::XK_q,
         action: Action::Quit,
     });
+    keys.push(KeyBinding {
+        mask: MOD | xlib::ControlMask | SHIFT_MASK,
+        keysym: keysym::XK_q,
+        action: Action::Restart,
+    });
     keys.push(KeyBinding {
         mask: MOD,
         keysym: keysym::XK_Tab,
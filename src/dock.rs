@@ -0,0 +1,48 @@
+use x11::xlib;
+
+use crate::*;
+
+impl Gmux {
+    /// Whether a window is a panel or dock, which is tracked instead of managed.
+    pub fn is_dock(&self, win: Window) -> bool {
+        let dock = self.net_atom(Net::WMWindowTypeDock) as i64;
+        self.xwrapper.get_long_property(win, self.net_atom(Net::WMWindowType), xlib::XA_ATOM)
+            .contains(&dock)
+    }
+
+    /// Shows a dock where it asked to be and keeps windows out of its struts.
+    pub fn add_dock(&mut self, win: Window) {
+        log::info!("Tracking dock {:?}", win);
+        self.xwrapper.select_input(win, xlib::PropertyChangeMask | xlib::StructureNotifyMask);
+        self.xwrapper.map_raised(win);
+        self.docks.insert(win, None);
+        self.update_dock_strut(win);
+    }
+
+    /// Rereads the strut of a dock, rearranging everything if it changed.
+    pub fn update_dock_strut(&mut self, win: Window) {
+        let partial = self.xwrapper.get_long_property(win, self.net_atom(Net::WMStrutPartial), xlib::XA_CARDINAL);
+        let strut = Strut::from_property(&partial).or_else(|| {
+            let full = self.xwrapper.get_long_property(win, self.net_atom(Net::WMStrut), xlib::XA_CARDINAL);
+            Strut::from_property(&full)
+        });
+        if self.docks.insert(win, strut) == Some(strut) {
+            return;
+        }
+        self.update_work_areas();
+    }
+
+    /// Forgets a dock that was unmapped or destroyed, giving back its space.
+    pub fn remove_dock(&mut self, win: Window) {
+        if let Some(Some(_)) = self.docks.remove(&win) {
+            self.update_work_areas();
+        }
+    }
+
+    fn update_work_areas(&mut self) {
+        for i in 0..self.mons.len() {
+            self.update_work_area(i);
+        }
+        self.arrange(None);
+    }
+}
@@ -0,0 +1,178 @@
+use x11::xlib;
+
+use crate::*;
+use crate::xwrapper::Net;
+
+/// `_NET_WM_MOVERESIZE` direction a client sends to end a drag it started.
+pub const NET_WM_MOVERESIZE_CANCEL: i64 = 11;
+
+/// How a window follows the pointer during an interactive move or resize.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DragMode {
+    Move,
+    /// Resize by the given edges, the others stay where they are.
+    Resize { left: bool, right: bool, top: bool, bottom: bool },
+}
+
+impl DragMode {
+    /// The mode for a `_NET_WM_MOVERESIZE` direction. Keyboard driven moves and
+    /// resizes and `NET_WM_MOVERESIZE_CANCEL` give `None`.
+    pub fn from_net_direction(dir: i64) -> Option<Self> {
+        let resize = |left, right, top, bottom| Some(DragMode::Resize { left, right, top, bottom });
+        match dir {
+            0 => resize(true, false, true, false), // top left
+            1 => resize(false, false, true, false), // top
+            2 => resize(false, true, true, false), // top right
+            3 => resize(false, true, false, false), // right
+            4 => resize(false, true, false, true), // bottom right
+            5 => resize(false, false, false, true), // bottom
+            6 => resize(true, false, false, true), // bottom left
+            7 => resize(true, false, false, false), // left
+            8 => Some(DragMode::Move),
+            _ => None,
+        }
+    }
+
+    /// Geometry of a window that was at `start` when the drag began, after the
+    /// pointer has moved by `dx`, `dy`. Windows never shrink below 1x1.
+    pub fn apply(&self, start: Rect, dx: i32, dy: i32) -> Rect {
+        match *self {
+            DragMode::Move => Rect::new(start.x + dx, start.y + dy, start.w, start.h),
+            DragMode::Resize { left, right, top, bottom } => {
+                let (mut x, mut w) = (start.x, start.w);
+                if left {
+                    let dx = dx.min(start.w - 1);
+                    x += dx;
+                    w -= dx;
+                } else if right {
+                    w = (w + dx).max(1);
+                }
+                let (mut y, mut h) = (start.y, start.h);
+                if top {
+                    let dy = dy.min(start.h - 1);
+                    y += dy;
+                    h -= dy;
+                } else if bottom {
+                    h = (h + dy).max(1);
+                }
+                Rect::new(x, y, w, h)
+            }
+        }
+    }
+}
+
+impl Gmux {
+    /// Moves or resizes a client with the pointer until the button is released,
+    /// like dwm's movemouse and resizemouse. A tiled client is floated first.
+    /// The client can end the drag early with `NET_WM_MOVERESIZE_CANCEL`.
+    pub fn drag_client(&mut self, handle: ClientHandle, mode: DragMode) {
+        let (win, start, mon_idx) = match self.clients.get(&handle) {
+            Some(c) if !c.is_fullscreen => (c.win, Rect::new(c.x, c.y, c.w, c.h), c.monitor_idx),
+            _ => return,
+        };
+        let buttons = xlib::Button1Mask | xlib::Button2Mask | xlib::Button3Mask | xlib::Button4Mask | xlib::Button5Mask;
+        let (px, py) = match self.xwrapper.query_pointer() {
+            // The button may already be up by the time a client asks for a
+            // drag, and no release would come to end it
+            Some((x, y, mask)) if mask & buttons != 0 => (x, y),
+            _ => return,
+        };
+        let cursor = match mode {
+            DragMode::Move => CursorType::Move,
+            DragMode::Resize { .. } => CursorType::Resize,
+        };
+        if !self.xwrapper.grab_pointer(self.root, self.cursor[cursor as usize]) {
+            return;
+        }
+
+        let tiled = !self.mons[mon_idx].layout().is_floating();
+        if let Some(c) = self.clients.get_mut(&handle).filter(|c| tiled && !c.is_floating) {
+            c.is_floating = true;
+            self.arrange(Some(mon_idx));
+        }
+        self.focus(Some(handle));
+        if let Some(c) = self.clients.get(&handle) {
+            self.xwrapper.raise_window(c.win);
+        }
+
+        let mut last_motion: xlib::Time = 0;
+        loop {
+            let mut ev = self.xwrapper.next_event_of(&[
+                xlib::ButtonRelease, xlib::MotionNotify, xlib::Expose,
+                xlib::ConfigureRequest, xlib::MapRequest, xlib::ClientMessage,
+            ]);
+            match ev.get_type() {
+                xlib::ConfigureRequest => unsafe { events::configure_request(self, &mut ev.configure_request) },
+                xlib::Expose => unsafe { events::expose(self, &mut ev.expose) },
+                xlib::MapRequest => unsafe { events::map_request(self, &mut ev.map_request) },
+                xlib::ClientMessage => {
+                    let cm = unsafe { ev.client_message };
+                    if cm.message_type != self.net_atom(Net::WMMoveResize) {
+                        unsafe { events::client_message(self, &mut ev.client_message) };
+                    } else if cm.window == win.0 && cm.data.get_long(2) == NET_WM_MOVERESIZE_CANCEL {
+                        break;
+                    }
+                }
+                xlib::MotionNotify => {
+                    let motion = unsafe { ev.motion };
+                    // At most 60 updates a second
+                    if motion.time.wrapping_sub(last_motion) <= 1000 / 60 {
+                        continue;
+                    }
+                    last_motion = motion.time;
+                    let r = mode.apply(start, motion.x_root - px, motion.y_root - py);
+                    self.resize(handle, r.x, r.y, r.w, r.h);
+                }
+                xlib::ButtonRelease => break,
+                _ => {}
+            }
+        }
+        self.xwrapper.ungrab_pointer();
+
+        // Dropped onto another monitor
+        let centre = self.clients.get(&handle).map(|c| (c.x + c.width() / 2, c.y + c.height() / 2));
+        if let Some((cx, cy)) = centre {
+            let target = self.mons.iter().position(|m| {
+                cx >= m.wx && cx < m.wx + m.ww && cy >= m.wy && cy < m.wy + m.wh
+            });
+            if let Some(target) = target.filter(|&t| t != mon_idx) {
+                self.send_to_monitor(handle, target);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_net_directions() {
+        assert_eq!(DragMode::from_net_direction(8), Some(DragMode::Move));
+        assert_eq!(
+            DragMode::from_net_direction(4),
+            Some(DragMode::Resize { left: false, right: true, top: false, bottom: true })
+        );
+        // Keyboard move and cancel
+        assert_eq!(DragMode::from_net_direction(10), None);
+        assert_eq!(DragMode::from_net_direction(NET_WM_MOVERESIZE_CANCEL), None);
+    }
+
+    #[test]
+    fn test_apply() {
+        let start = Rect::new(100, 100, 200, 150);
+        assert_eq!(DragMode::Move.apply(start, -30, 40), Rect::new(70, 140, 200, 150));
+
+        let bottom_right = DragMode::from_net_direction(4).unwrap();
+        assert_eq!(bottom_right.apply(start, 50, -20), Rect::new(100, 100, 250, 130));
+        assert_eq!(bottom_right.apply(start, -500, -500), Rect::new(100, 100, 1, 1));
+
+        // Left and top edges move while the opposite ones stay put
+        let top_left = DragMode::from_net_direction(0).unwrap();
+        assert_eq!(top_left.apply(start, -10, 20), Rect::new(90, 120, 210, 130));
+        assert_eq!(top_left.apply(start, 500, 500), Rect::new(299, 249, 1, 1));
+
+        let top = DragMode::from_net_direction(1).unwrap();
+        assert_eq!(top.apply(start, 80, -10), Rect::new(100, 90, 200, 160));
+    }
+}
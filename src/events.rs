@@ -179,6 +179,8 @@ pub unsafe fn unmap_notify(state: &mut Gmux, ev: &mut xlib::XUnmapEvent) {
             log::info!("UnmapNotify: real event for window {:?}, unmanaging", ev.window);
             state.unmanage(handle, false);
         }
+    } else {
+        state.remove_dock(crate::xwrapper::Window(ev.window));
     }
 }
 
@@ -186,6 +188,10 @@ pub unsafe fn unmap_notify(state: &mut Gmux, ev: &mut xlib::XUnmapEvent) {
 pub unsafe fn destroy_notify(state: &mut Gmux, ev: &mut xlib::XDestroyWindowEvent) {
     if let Some(handle) = state.window_to_client_handle(ev.window) {
         state.unmanage(handle, true);
+    } else {
+        // A swallowed terminal that went away on its own
+        state.swallowed.retain(|_, term| term.win.0 != ev.window);
+        state.remove_dock(crate::xwrapper::Window(ev.window));
     }
 }
 
@@ -247,20 +253,46 @@ pub unsafe fn enter_notify(state: &mut Gmux, ev: &mut xlib::XCrossingEvent) {
     }
 }
 
+/// Takes the focus back for the selected client when another window grabbed it
+pub fn focus_in(state: &mut Gmux, ev: &mut xlib::XFocusChangeEvent) {
+    let sel = state.mons[state.selected_monitor].sel;
+    if let Some(handle) = sel.filter(|h| state.clients.get(h).is_some_and(|c| c.win.0 != ev.window)) {
+        state.set_focus(handle);
+    }
+}
+
 pub unsafe fn map_request(state: &mut Gmux, ev: &mut xlib::XMapRequestEvent) {
     if let Ok(mut wa) = state.xwrapper.get_window_attributes(crate::xwrapper::Window(ev.window)) {
         if wa.override_redirect != 0 {
             return;
         }
-        if state.window_to_client_handle(ev.window).is_none() {
-            unsafe { state.manage(ev.window, &mut wa) };
+        match state.window_to_client_handle(ev.window) {
+            None => unsafe { state.manage(ev.window, &mut wa, false) },
+            // An iconified client asking to be shown again
+            Some(handle) if state.clients.get(&handle).is_some_and(|c| c.is_hidden) => {
+                state.activate_client(handle);
+            }
+            Some(_) => {}
         }
     }
 }
 
 pub unsafe fn property_notify(state: &mut Gmux, ev: &mut xlib::XPropertyEvent) {
+    let win = crate::xwrapper::Window(ev.window);
+    if state.docks.contains_key(&win) {
+        if ev.atom == state.net_atom(crate::xwrapper::Net::WMStrut)
+            || ev.atom == state.net_atom(crate::xwrapper::Net::WMStrutPartial)
+        {
+            state.update_dock_strut(win);
+        }
+        return;
+    }
     // Check if the event is for a window we manage
     if let Some(handle) = state.window_to_client_handle(ev.window) {
+        if ev.atom == xlib::XA_WM_HINTS {
+            state.update_wm_hints(handle);
+            return;
+        }
         if let Some(client) = state.clients.get_mut(&handle) {
             // We only care about name changes.
             // _NET_WM_NAME is the modern, UTF-8 compatible standard.
@@ -282,55 +314,105 @@ pub unsafe fn property_notify(state: &mut Gmux, ev: &mut xlib::XPropertyEvent) {
     }
 }
 
-/// Handles ClientMessage events, particularly NetActiveWindow and NetWMState requests
+/// Handles ClientMessage events: EWMH requests from clients and from pagers and
+/// tools like wmctrl and xdotool
 pub unsafe fn client_message(state: &mut Gmux, ev: &mut xlib::XClientMessageEvent) {
-    if let Some(handle) = state.window_to_client_handle(ev.window) {
-        if ev.message_type == state.xwrapper.atoms.get(crate::xwrapper::Atom::Net(crate::xwrapper::Net::WMState)) {
-            // Handle _NET_WM_STATE fullscreen requests
-            let net_wm_fullscreen = state.xwrapper.atoms.get(crate::xwrapper::Atom::Net(crate::xwrapper::Net::WMFullscreen));
-            
-            if ev.data.get_long(1) == net_wm_fullscreen as i64 || ev.data.get_long(2) == net_wm_fullscreen as i64 {
-                // _NET_WM_STATE_ADD = 1, _NET_WM_STATE_REMOVE = 0, _NET_WM_STATE_TOGGLE = 2
-                let should_fullscreen = match ev.data.get_long(0) {
-                    1 => true,  // _NET_WM_STATE_ADD
-                    0 => false, // _NET_WM_STATE_REMOVE  
-                    2 => {      // _NET_WM_STATE_TOGGLE
-                        if let Some(client) = state.clients.get(&handle) {
-                            !client.is_fullscreen
-                        } else {
-                            false
-                        }
-                    },
-                    _ => false,
-                };
-                
-                log::info!("Client requested fullscreen: {}", should_fullscreen);
-                state.setfullscreen(handle, should_fullscreen);
+    use crate::xwrapper::Net;
+
+    if ev.window == state.root.0 {
+        if ev.message_type == state.net_atom(Net::CurrentDesktop) {
+            let desktop = ev.data.get_long(0);
+            if (0..TAGS.len() as i64).contains(&desktop) {
+                Action::ViewTag(1 << desktop, None).execute(state);
             }
-            
-        } else if ev.message_type == state.xwrapper.atoms.get(crate::xwrapper::Atom::Net(crate::xwrapper::Net::ActiveWindow)) {
-            let should_mark_urgent = if let Some(client) = state.clients.get(&handle) {
-                // If this is not the currently selected client and it's not already urgent, set urgent
-                let is_selected = state.mons[state.selected_monitor].sel == Some(handle);
-                !is_selected && !client.is_urgent
-            } else {
-                false
+        }
+        return;
+    }
+
+    let handle = match state.window_to_client_handle(ev.window) {
+        Some(h) => h,
+        None => return,
+    };
+    if ev.message_type == state.net_atom(Net::WMState) {
+        // _NET_WM_STATE_REMOVE = 0, _NET_WM_STATE_ADD = 1, _NET_WM_STATE_TOGGLE = 2
+        let action = ev.data.get_long(0);
+        let wants = |current: bool| match action {
+            0 => false,
+            1 => true,
+            2 => !current,
+            _ => current,
+        };
+        for prop in [ev.data.get_long(1), ev.data.get_long(2)] {
+            let c = match state.clients.get(&handle) {
+                Some(c) => c,
+                None => return,
             };
-            
-            if should_mark_urgent {
-                let client_name = if let Some(client) = state.clients.get_mut(&handle) {
-                    client.is_urgent = true;
-                    client.name.clone()
-                } else {
-                    String::new()
-                };
-                
-                if !client_name.is_empty() {
-                    // Redraw bars to show urgent indicator
-                    state.draw_bars();
-                    log::info!("Window '{}' requested focus, marked as urgent", client_name);
-                }
+            let prop = prop as xlib::Atom;
+            if prop == 0 {
+                continue;
+            } else if prop == state.net_atom(Net::WMFullscreen) {
+                let fullscreen = wants(c.is_fullscreen);
+                log::info!("Client requested fullscreen: {}", fullscreen);
+                state.setfullscreen(handle, fullscreen);
+            } else if prop == state.net_atom(Net::WMSticky) {
+                let sticky = wants(c.is_sticky);
+                log::info!("Client requested sticky: {}", sticky);
+                state.set_sticky(handle, sticky);
+            } else if prop == state.net_atom(Net::WMAbove) {
+                let above = wants(c.is_above);
+                state.set_above(handle, above);
+            } else if prop == state.net_atom(Net::WMHidden) {
+                let hidden = wants(c.is_hidden);
+                state.set_hidden(handle, hidden);
+            } else if prop == state.net_atom(Net::WMDemandsAttention) {
+                let urgent = wants(c.is_urgent);
+                state.set_urgent(handle, urgent);
             }
         }
+    } else if ev.message_type == state.net_atom(Net::ActiveWindow) {
+        // If this is not the currently selected client, mark it urgent
+        let is_selected = state.mons[state.selected_monitor].sel == Some(handle);
+        if !is_selected && state.clients.get(&handle).is_some_and(|c| !c.is_urgent) {
+            state.set_urgent(handle, true);
+            log::info!("Window {:?} requested focus, marked as urgent", ev.window);
+        }
+    } else if ev.message_type == state.net_atom(Net::CloseWindow) {
+        state.kill_client(handle);
+    } else if ev.message_type == state.net_atom(Net::MoveResizeWindow) {
+        let c = match state.clients.get(&handle) {
+            Some(c) => c,
+            None => return,
+        };
+        if c.is_floating || state.mons[c.monitor_idx].layout().is_floating() {
+            // Bits 8 to 11 of the flags say which of x, y, width and height are set
+            let flags = ev.data.get_long(0);
+            let field = |bit: i64, current: i32| {
+                if flags & (1 << (8 + bit)) != 0 { ev.data.get_long(1 + bit as usize) as i32 } else { current }
+            };
+            let (x, y, w, h) = (field(0, c.x), field(1, c.y), field(2, c.w), field(3, c.h));
+            state.resize(handle, x, y, w.max(1), h.max(1));
+        } else {
+            state.send_configure_notify(handle);
+        }
+    } else if ev.message_type == state.net_atom(Net::WMMoveResize) {
+        // A cancel only means something while `drag_client` runs, which handles it
+        if let Some(mode) = DragMode::from_net_direction(ev.data.get_long(2)) {
+            state.drag_client(handle, mode);
+        }
+    } else if ev.message_type == state.net_atom(Net::WMDesktop) {
+        let desktop = ev.data.get_long(0);
+        if desktop as u32 == u32::MAX {
+            state.set_sticky(handle, true);
+        } else if (0..TAGS.len() as i64).contains(&desktop) {
+            state.set_sticky(handle, false);
+            let mon_idx = match state.clients.get_mut(&handle) {
+                Some(c) => {
+                    c.tags = 1 << desktop;
+                    c.monitor_idx
+                }
+                None => return,
+            };
+            state.arrange(Some(mon_idx));
+        }
     }
 }
@@ -18,6 +18,30 @@ pub struct LauncherEntry {
 
 pub const LAUNCHER_PROPORTION: f32 = 0.381953;
 
+/// What the launcher is choosing from.
+#[derive(Debug, Clone)]
+pub enum LauncherMode {
+    /// Applications from `all_commands`.
+    Commands,
+    /// Managed windows in most recently used order, with their labels.
+    Windows(Vec<(ClientHandle, String)>),
+}
+
+/// Display name of candidate `idx`.
+fn candidate_name<'a>(mode: &'a LauncherMode, commands: &'a [LauncherEntry], idx: usize) -> &'a str {
+    match mode {
+        LauncherMode::Commands => &commands[idx].name,
+        LauncherMode::Windows(windows) => &windows[idx].1,
+    }
+}
+
+fn candidate_count(mode: &LauncherMode, commands: &[LauncherEntry]) -> usize {
+    match mode {
+        LauncherMode::Commands => commands.len(),
+        LauncherMode::Windows(windows) => windows.len(),
+    }
+}
+
 /// Process desktop file field codes in Exec strings.
 /// According to Desktop Entry Specification, these codes should be handled:
 /// %f - single file path
@@ -133,8 +157,10 @@ impl Gmux {
     pub fn handle_launcher_keypress(&mut self, kev: &xlib::XKeyEvent) {
         let mut new_state = None;
         let mut command_to_run = None;
+        let mut window_to_focus = None;
 
         if let BarState::Launcher {
+            mode,
             input,
             candidate_indices,
             selected_idx,
@@ -151,8 +177,15 @@ impl Gmux {
                 }
                 keysym::XK_Return => {
                     if !candidate_indices.is_empty() {
-                        let command_idx = candidate_indices[*selected_idx];
-                        command_to_run = Some(self.all_commands[command_idx].exec.clone());
+                        let idx = candidate_indices[*selected_idx];
+                        match mode {
+                            LauncherMode::Commands => {
+                                command_to_run = Some(self.all_commands[idx].exec.clone());
+                            }
+                            LauncherMode::Windows(windows) => {
+                                window_to_focus = Some(windows[idx].0);
+                            }
+                        }
                     }
                     new_state = Some(BarState::Normal);
                     self.xwrapper.ungrab_keyboard();
@@ -187,11 +220,11 @@ impl Gmux {
 
             if dirty {
                 let matcher = SkimMatcherV2::default();
-                let mut new_candidates: Vec<(i64, usize)> = self
-                    .all_commands
-                    .iter()
-                    .enumerate()
-                    .filter_map(|(i, entry)| matcher.fuzzy_match(&entry.name, input).map(|score| (score, i)))
+                let mut new_candidates: Vec<(i64, usize)> = (0..candidate_count(mode, &self.all_commands))
+                    .filter_map(|i| {
+                        let name = candidate_name(mode, &self.all_commands, i);
+                        matcher.fuzzy_match(name, input).map(|score| (score, i))
+                    })
                     .collect();
 
                 new_candidates.sort_by(|a, b| b.0.cmp(&a.0));
@@ -208,6 +241,10 @@ impl Gmux {
             self.bar_state = state;
         }
 
+        if let Some(handle) = window_to_focus {
+            self.activate_client(handle);
+        }
+
         self.draw_bars();
     }
 
@@ -217,6 +254,7 @@ impl Gmux {
         log::info!("Launcher entered: {} commands loaded", self.all_commands.len());
         let initial_candidates = (0..self.all_commands.len()).collect();
         self.bar_state = BarState::Launcher {
+            mode: LauncherMode::Commands,
             prompt: "> ".to_string(),
             input: String::new(),
             candidate_indices: initial_candidates,
@@ -226,6 +264,33 @@ impl Gmux {
         self.draw_bars();
     }
 
+    /// Opens the launcher on the selected monitor's windows, most recently used
+    /// first, across all of its tags.
+    pub fn enter_window_switcher(&mut self) {
+        let mon = &self.mons[self.selected_monitor];
+        let windows: Vec<(ClientHandle, String)> = mon.mru_clients().into_iter()
+            .filter_map(|h| {
+                let c = self.clients.get(&h)?;
+                let tag = (0..TAGS.len()).find(|i| c.tags & (1 << i) != 0);
+                let label = match tag {
+                    Some(i) => format!("{}:{}", TAGS[i], c.name),
+                    None => c.name.clone(),
+                };
+                Some((h, label))
+            })
+            .collect();
+        log::info!("Window switcher entered: {} windows", windows.len());
+        self.bar_state = BarState::Launcher {
+            prompt: "window: ".to_string(),
+            input: String::new(),
+            candidate_indices: (0..windows.len()).collect(),
+            selected_idx: 0,
+            mode: LauncherMode::Windows(windows),
+        };
+        self.xwrapper.grab_keyboard(self.root);
+        self.draw_bars();
+    }
+
     // This function is now refactored to use a cache and focus on .desktop files.
     pub fn get_commands() -> Vec<LauncherEntry> {
         log::info!("get_commands() called - loading launcher entries");
@@ -343,9 +408,9 @@ impl Gmux {
     }
 
     pub fn draw_launcher_bar(&mut self, mon_idx: usize) {
-        let (prompt, input, candidate_indices, selected_idx) =
-            if let BarState::Launcher { prompt, input, candidate_indices, selected_idx, .. } = &self.bar_state {
-                (prompt.clone(), input.clone(), candidate_indices.clone(), *selected_idx)
+        let (mode, prompt, input, candidate_indices, selected_idx) =
+            if let BarState::Launcher { mode, prompt, input, candidate_indices, selected_idx } = &self.bar_state {
+                (mode.clone(), prompt.clone(), input.clone(), candidate_indices.clone(), *selected_idx)
             } else {
                 return;
             };
@@ -377,8 +442,8 @@ impl Gmux {
 
         let candidate_widths: Vec<i32> = candidate_indices
             .iter()
-            .map(|&command_idx| {
-                let candidate = &self.all_commands[command_idx].name;
+            .map(|&idx| {
+                let candidate = candidate_name(&mode, &self.all_commands, idx);
                 (self.get_text_width(candidate)) as i32
             })
             .collect();
@@ -402,8 +467,7 @@ impl Gmux {
             let draw_pos_x = pos_x + current_x - offset;
 
             if draw_pos_x + w > pos_x && draw_pos_x < self.mons[mon_idx].ww as i32 {
-                let command_idx = candidate_indices[i];
-                let candidate = &self.all_commands[command_idx].name;
+                let candidate = candidate_name(&mode, &self.all_commands, candidate_indices[i]);
                 let wh = ivec2(w as _, self.bar_height);
 
                 let (bg_col, fg_col) = if i == selected_idx {
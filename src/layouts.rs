@@ -1,114 +1,670 @@
-use crate::state::{Gmux};
-use crate::ClientHandle;
-
-#[derive(Debug)]
-pub struct Layout {
-    pub symbol: &'static str,
-    pub arrange: Option<fn(&mut Gmux, usize)>,
-}
-
-pub static LAYOUTS: [Layout; 3] = [
-    Layout {
-        symbol: "[]=",
-        arrange: Some(tile),
-    },
-    Layout {
-        symbol: "><>",
-        arrange: Some(monocle),
-    },
-    Layout {
-        symbol: "[M]",
-        arrange: Some(monocle),
-    },
+use crate::{ClientHandle, Monitor, TreeLayout};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+/// Client geometry as passed to `Gmux::resize`, i.e. excluding the border.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub w: i32,
+    pub h: i32,
+}
+
+impl Rect {
+    pub fn new(x: i32, y: i32, w: i32, h: i32) -> Self {
+        Rect { x, y, w, h }
+    }
+}
+
+impl Rect {
+    fn centre(&self) -> (i32, i32) {
+        (self.x + self.w / 2, self.y + self.h / 2)
+    }
+}
+
+/// Picks the candidate closest to `from` in a direction. Only candidates whose
+/// centre lies beyond `from`'s edge on that side count. Those that overlap `from` across the
+/// direction of travel (e.g. share some rows when moving right) are preferred,
+/// then the smallest gap between the facing edges, then the smallest sideways
+/// offset between centres.
+pub fn nearest_in_direction<T: Copy>(from: Rect, candidates: &[(T, Rect)], dir: Direction) -> Option<T> {
+    let (fx, fy) = from.centre();
+    candidates.iter()
+        .filter_map(|&(t, r)| {
+            let (cx, cy) = r.centre();
+            let (ahead, gap, overlap, sideways) = match dir {
+                Direction::Left => (cx < from.x, from.x - (r.x + r.w), r.y < from.y + from.h && from.y < r.y + r.h, cy - fy),
+                Direction::Right => (cx > from.x + from.w, r.x - (from.x + from.w), r.y < from.y + from.h && from.y < r.y + r.h, cy - fy),
+                Direction::Up => (cy < from.y, from.y - (r.y + r.h), r.x < from.x + from.w && from.x < r.x + r.w, cx - fx),
+                Direction::Down => (cy > from.y + from.h, r.y - (from.y + from.h), r.x < from.x + from.w && from.x < r.x + r.w, cx - fx),
+            };
+            ahead.then_some(((!overlap, gap.max(0), sideways.abs()), t))
+        })
+        .min_by_key(|(key, _)| *key)
+        .map(|(_, t)| t)
+}
+
+/// A tiled client as seen by a layout.
+#[derive(Debug, Clone, Copy)]
+pub struct LayoutClient {
+    pub handle: ClientHandle,
+    pub bw: i32,
+    /// Relative height of the client within its column.
+    pub cfact: f32,
+    pub min_width: i32,
+    pub min_height: i32,
+}
+
+/// Gap sizes in effect for the current arrangement. Smart gaps and the gap toggle
+/// have already been applied, so layouts use these as-is.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Gaps {
+    pub oh: i32,
+    pub ov: i32,
+    pub ih: i32,
+    pub iv: i32,
+}
+
+impl Gaps {
+    /// The gaps of a monitor arranging `tiled` clients. Smart gaps drop the
+    /// outer gaps around a lone client.
+    pub fn for_monitor(mon: &Monitor, tiled: usize, smart: bool) -> Gaps {
+        if !mon.gaps_enabled {
+            return Gaps::default();
+        }
+        let outer = !(smart && tiled == 1);
+        Gaps {
+            oh: if outer { mon.gap_oh } else { 0 },
+            ov: if outer { mon.gap_ov } else { 0 },
+            ih: mon.gap_ih,
+            iv: mon.gap_iv,
+        }
+    }
+}
+
+/// Everything a layout needs to compute an arrangement. Built by `Gmux` for one
+/// monitor; layouts never touch X or the rest of the window manager state.
+#[derive(Debug, Clone)]
+pub struct LayoutInput {
+    /// The monitor's window area.
+    pub area: Rect,
+    /// Tiled clients in stack order.
+    pub clients: Vec<LayoutClient>,
+    /// Number of visible clients, floating ones included.
+    pub visible: usize,
+    pub focused: Option<ClientHandle>,
+    pub tagset: u32,
+    pub mfact: f32,
+    pub nmaster: i32,
+    pub gaps: Gaps,
+    /// Cells are not split further once they would get smaller than this.
+    pub min_cell: i32,
+}
+
+pub trait Layout: std::fmt::Debug {
+    /// Name the layout is registered under.
+    fn name(&self) -> &'static str;
+
+    /// Default bar symbol.
+    fn symbol(&self) -> &'static str;
+
+    /// Bar symbol for a specific arrangement, e.g. one that counts clients.
+    fn symbol_for(&self, _input: &LayoutInput) -> String {
+        self.symbol().to_string()
+    }
+
+    /// Returns one rectangle per client in `input.clients`, in the same order.
+    fn arrange(&mut self, input: &LayoutInput) -> Vec<Rect>;
+
+    /// Floating layouts leave every client where it is.
+    fn is_floating(&self) -> bool {
+        false
+    }
+
+    /// Tiled clients fill the whole work area, so their borders serve no purpose.
+    fn is_monocle(&self) -> bool {
+        false
+    }
+
+    /// Tiled clients that have to be stacked above the others after the last
+    /// arrange, e.g. the visible child of a tabbed container.
+    fn raised(&self) -> &[ClientHandle] {
+        &[]
+    }
+
+    /// Called when one client takes over another's place, e.g. when a program
+    /// swallows the terminal it was started from.
+    fn replace_client(&mut self, _old: ClientHandle, _new: ClientHandle) {}
+
+    /// State to carry over an in-place restart, as a single line of text
+    /// without tabs.
+    fn save_state(&self) -> Option<String> {
+        None
+    }
+
+    /// Takes back what `save_state` returned in the previous process.
+    fn restore_state(&mut self, _state: &str) {}
+
+    /// Gives `Action::Tree` access to the manual tiling tree, if this is one.
+    fn as_tree(&mut self) -> Option<&mut TreeLayout> {
+        None
+    }
+
+    fn box_clone(&self) -> Box<dyn Layout>;
+}
+
+impl Clone for Box<dyn Layout> {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
+}
+
+fn make<L: Layout + Default + 'static>() -> Box<dyn Layout> {
+    Box::new(L::default())
+}
+
+type LayoutCtor = fn() -> Box<dyn Layout>;
+
+/// Every layout that can be selected by name, e.g. through `Action::SetLayout`.
+pub static LAYOUTS: &[(&str, LayoutCtor)] = &[
+    ("tile", make::<Tile>),
+    ("float", make::<Floating>),
+    ("monocle", make::<Monocle>),
+    ("deck", make::<Deck>),
+    ("spiral", make::<Spiral>),
+    ("dwindle", make::<Dwindle>),
+    ("tree", make::<TreeLayout>),
 ];
 
-fn tile(state: &mut Gmux, mon_idx: usize) {
-    let mon = &state.mons[mon_idx];
-    let tiled_clients: Vec<ClientHandle> = mon.stack.iter()
-        .filter(|h| state.clients.get(h).map_or(false, |c| !c.is_floating && c.is_visible_on(mon)))
-        .cloned()
-        .collect();
+/// Creates a fresh instance of the layout registered under `name`.
+pub fn layout_by_name(name: &str) -> Option<Box<dyn Layout>> {
+    LAYOUTS.iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, ctor)| ctor())
+}
 
-    let n = tiled_clients.len();
-    if n == 0 {
-        return;
+/// Master and stack column geometry shared by `tile` and `deck`:
+/// (master x, stack x, master width, stack width).
+fn columns(input: &LayoutInput, nmaster: usize) -> (i32, i32, i32, i32) {
+    let Gaps { ov, iv, .. } = input.gaps;
+    let n = input.clients.len();
+    let mx = input.area.x + ov;
+    let mut sx = mx;
+    let mut mw = input.area.w - 2 * ov;
+    let mut sw = mw;
+
+    if nmaster > 0 && n > nmaster {
+        let total = mw - iv;
+        mw = (total as f32 * input.mfact) as i32;
+        sw = total - mw;
+        sx = mx + mw + iv;
+    }
+    (mx, sx, mw, sw)
+}
+
+/// Stacks `clients` vertically in a column, splitting the height by their weights.
+fn column(clients: &[LayoutClient], x: i32, y: i32, w: i32, h: i32, ih: i32, out: &mut Vec<Rect>) {
+    let mut facts: f32 = clients.iter().map(|c| c.cfact).sum();
+    let mut rest = h - ih * (clients.len() as i32 - 1);
+    let mut y = y;
+    for c in clients {
+        let ch = (rest as f32 * (c.cfact / facts)) as i32;
+        facts -= c.cfact;
+        rest -= ch;
+        out.push(Rect::new(x, y, w - 2 * c.bw, ch - 2 * c.bw));
+        y += ch + ih;
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Tile;
+
+impl Layout for Tile {
+    fn name(&self) -> &'static str {
+        "tile"
+    }
+
+    fn symbol(&self) -> &'static str {
+        "[]="
+    }
+
+    fn arrange(&mut self, input: &LayoutInput) -> Vec<Rect> {
+        let n = input.clients.len();
+        let mut rects = Vec::with_capacity(n);
+        if n == 0 {
+            return rects;
+        }
+
+        let Gaps { oh, ih, .. } = input.gaps;
+        let nmaster = (input.nmaster.max(0) as usize).min(n);
+        let (mx, sx, mw, sw) = columns(input, nmaster);
+        let y = input.area.y + oh;
+        let h = input.area.h - 2 * oh;
+
+        // Each column is split in proportion to the clients' size weights
+        column(&input.clients[..nmaster], mx, y, mw, h, ih, &mut rects);
+        column(&input.clients[nmaster..], sx, y, sw, h, ih, &mut rects);
+        rects
+    }
+
+    fn box_clone(&self) -> Box<dyn Layout> {
+        Box::new(self.clone())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Floating;
+
+impl Layout for Floating {
+    fn name(&self) -> &'static str {
+        "float"
+    }
+
+    fn symbol(&self) -> &'static str {
+        "><>"
+    }
+
+    fn arrange(&mut self, _input: &LayoutInput) -> Vec<Rect> {
+        Vec::new()
+    }
+
+    fn is_floating(&self) -> bool {
+        true
+    }
+
+    fn box_clone(&self) -> Box<dyn Layout> {
+        Box::new(self.clone())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Monocle;
+
+impl Layout for Monocle {
+    fn name(&self) -> &'static str {
+        "monocle"
+    }
+
+    fn symbol(&self) -> &'static str {
+        "[M]"
+    }
+
+    /// Shows how many windows are stacked up behind the selected one.
+    fn symbol_for(&self, input: &LayoutInput) -> String {
+        if input.visible > 0 {
+            format!("[{}]", input.visible)
+        } else {
+            self.symbol().to_string()
+        }
+    }
+
+    fn arrange(&mut self, input: &LayoutInput) -> Vec<Rect> {
+        let Gaps { oh, ov, .. } = input.gaps;
+        let a = input.area;
+        input.clients.iter()
+            .map(|c| Rect::new(
+                a.x + ov,
+                a.y + oh,
+                a.w - 2 * ov - 2 * c.bw,
+                a.h - 2 * oh - 2 * c.bw,
+            ))
+            .collect()
+    }
+
+    fn is_monocle(&self) -> bool {
+        true
+    }
+
+    fn box_clone(&self) -> Box<dyn Layout> {
+        Box::new(self.clone())
+    }
+}
+
+/// Like `tile`, except the stack area shows a single client at a time. All stack
+/// clients get the full stack area and `FocusStack` cycles through them.
+#[derive(Debug, Clone, Default)]
+pub struct Deck;
+
+impl Layout for Deck {
+    fn name(&self) -> &'static str {
+        "deck"
     }
 
-    let nmaster = mon.nmaster;
-    let mfact = mon.mfact;
-    let ww = mon.ww;
-    let wh = mon.wh;
-    let wx = mon.wx;
-    let wy = mon.wy;
+    fn symbol(&self) -> &'static str {
+        "[D]"
+    }
 
-    let mw = if n > nmaster as usize {
-        if nmaster > 0 {
-            (ww as f32 * mfact) as i32
+    fn symbol_for(&self, input: &LayoutInput) -> String {
+        let nmaster = input.nmaster.max(0) as usize;
+        if input.clients.len() > nmaster {
+            format!("[D{}]", input.clients.len() - nmaster)
         } else {
-            0
+            self.symbol().to_string()
+        }
+    }
+
+    fn arrange(&mut self, input: &LayoutInput) -> Vec<Rect> {
+        let n = input.clients.len();
+        let mut rects = Vec::with_capacity(n);
+        if n == 0 {
+            return rects;
         }
-    } else {
-        ww
-    };
-
-    let mut my = 0;
-    let mut ty = 0;
-
-    for (i, &handle) in tiled_clients.iter().enumerate() {
-        if let Some(client) = state.clients.get(&handle) {
-            let client_bw = client.bw;
-
-            if i < nmaster as usize {
-                let h = (wh - my) / (std::cmp::min(n, nmaster as usize) - i) as i32;
-                state.resize(
-                    handle,
-                    wx,
-                    wy + my,
-                    mw - (2 * client_bw),
-                    h - (2 * client_bw),
-                );
-                if my + h < wh {
-                    my += h;
+
+        let Gaps { oh, ih, .. } = input.gaps;
+        let nmaster = (input.nmaster.max(0) as usize).min(n);
+        let (mx, sx, mw, sw) = columns(input, nmaster);
+        let y = input.area.y + oh;
+        let h = input.area.h - 2 * oh;
+
+        column(&input.clients[..nmaster], mx, y, mw, h, ih, &mut rects);
+        for c in &input.clients[nmaster..] {
+            rects.push(Rect::new(sx, y, sw - 2 * c.bw, h - 2 * c.bw));
+        }
+        rects
+    }
+
+    fn box_clone(&self) -> Box<dyn Layout> {
+        Box::new(self.clone())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Spiral;
+
+impl Layout for Spiral {
+    fn name(&self) -> &'static str {
+        "spiral"
+    }
+
+    fn symbol(&self) -> &'static str {
+        "[@]"
+    }
+
+    fn arrange(&mut self, input: &LayoutInput) -> Vec<Rect> {
+        fibonacci(input, false)
+    }
+
+    fn box_clone(&self) -> Box<dyn Layout> {
+        Box::new(self.clone())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Dwindle;
+
+impl Layout for Dwindle {
+    fn name(&self) -> &'static str {
+        "dwindle"
+    }
+
+    fn symbol(&self) -> &'static str {
+        "[\\]"
+    }
+
+    fn arrange(&mut self, input: &LayoutInput) -> Vec<Rect> {
+        fibonacci(input, true)
+    }
+
+    fn box_clone(&self) -> Box<dyn Layout> {
+        Box::new(self.clone())
+    }
+}
+
+/// Each client takes half of the space left over by the previous one, alternating
+/// between vertical and horizontal splits. The first split is governed by `mfact`.
+/// `dwindle` keeps shrinking towards the bottom right, while the spiral variant
+/// turns inwards clockwise.
+fn fibonacci(input: &LayoutInput, dwindle: bool) -> Vec<Rect> {
+    let n = input.clients.len();
+    let mut rects = Vec::with_capacity(n);
+    if n == 0 {
+        return rects;
+    }
+
+    let Gaps { oh, ov, ih, iv } = input.gaps;
+    let Rect { x: wx, y: wy, w: ww, h: wh } = input.area;
+
+    let mut nx = wx + ov;
+    let mut ny = wy + oh;
+    let mut nw = ww - 2 * ov;
+    let mut nh = wh - 2 * oh;
+    // Pixels lost to integer division, handed back to the client that keeps
+    // the larger half so the cells line up with the outer gaps.
+    let mut hrest = 0;
+    let mut wrest = 0;
+    let mut splitting = true;
+    let mut i = 0;
+
+    for c in &input.clients {
+        if splitting {
+            // Stop splitting once a half would be unusably small; the
+            // remaining clients then share the last cell.
+            if (i % 2 == 1 && (nh - ih) / 2 <= input.min_cell.max(c.min_height) + 2 * c.bw)
+                || (i % 2 == 0 && (nw - iv) / 2 <= input.min_cell.max(c.min_width) + 2 * c.bw)
+            {
+                splitting = false;
+            }
+            if splitting && i < n - 1 {
+                if i % 2 == 1 {
+                    let nv = (nh - ih) / 2;
+                    hrest = nh - 2 * nv - ih;
+                    nh = nv;
+                } else {
+                    let nv = (nw - iv) / 2;
+                    wrest = nw - 2 * nv - iv;
+                    nw = nv;
+                }
+                if i % 4 == 2 && !dwindle {
+                    nx += nw + iv;
+                } else if i % 4 == 3 && !dwindle {
+                    ny += nh + ih;
+                }
+            }
+            match i % 4 {
+                0 => {
+                    if dwindle {
+                        ny += nh + ih;
+                        nh += hrest;
+                    } else {
+                        nh -= hrest;
+                        ny -= nh + ih;
+                    }
+                }
+                1 => {
+                    nx += nw + iv;
+                    nw += wrest;
+                }
+                2 => {
+                    ny += nh + ih;
+                    nh += hrest;
+                    if i < n - 1 {
+                        nw += wrest;
+                    }
                 }
-            } else {
-                let h = (wh - ty) / (n - i) as i32;
-                state.resize(
-                    handle,
-                    wx + mw,
-                    wy + ty,
-                    ww - mw - (2 * client_bw),
-                    h - (2 * client_bw),
-                );
-                if ty + h < wh {
-                    ty += h;
+                _ => {
+                    if dwindle {
+                        nx += nw + iv;
+                        nw -= wrest;
+                    } else {
+                        nw -= wrest;
+                        nx -= nw + iv;
+                        nh += hrest;
+                    }
                 }
             }
+            if i == 0 {
+                if n != 1 {
+                    nw = ((ww - iv - 2 * ov) as f32 * input.mfact) as i32;
+                    wrest = 0;
+                }
+                ny = wy + oh;
+            } else if i == 1 {
+                nw = ww - nw - iv - 2 * ov;
+            }
+            i += 1;
         }
+
+        rects.push(Rect::new(nx, ny, nw - 2 * c.bw, nh - 2 * c.bw));
     }
+    rects
 }
 
-fn monocle(state: &mut Gmux, mon_idx: usize) {
-    let mon = &state.mons[mon_idx];
-    let tiled_clients: Vec<ClientHandle> = mon.stack.iter()
-        .filter(|h| state.clients.get(h).map_or(false, |c| !c.is_floating && c.is_visible_on(mon)))
-        .cloned()
-        .collect();
-
-    let wx = mon.wx;
-    let wy = mon.wy;
-    let ww = mon.ww;
-    let wh = mon.wh;
-
-    for &handle in &tiled_clients {
-        if let Some(client) = state.clients.get(&handle) {
-            let client_bw = client.bw;
-            state.resize(
-                handle,
-                wx,
-                wy,
-                ww - 2 * client_bw,
-                wh - 2 * client_bw,
-            );
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(id: u64, cfact: f32) -> LayoutClient {
+        LayoutClient {
+            handle: ClientHandle::from(crate::Window(id)),
+            bw: 0,
+            cfact,
+            min_width: 0,
+            min_height: 0,
+        }
+    }
+
+    fn input(clients: Vec<LayoutClient>) -> LayoutInput {
+        LayoutInput {
+            area: Rect::new(0, 20, 1000, 800),
+            visible: clients.len(),
+            clients,
+            focused: None,
+            tagset: 1,
+            mfact: 0.6,
+            nmaster: 1,
+            gaps: Gaps::default(),
+            min_cell: 20,
+        }
+    }
+
+    #[test]
+    fn test_tile_master_and_stack() {
+        let rects = Tile.arrange(&input(vec![client(1, 1.0), client(2, 1.0), client(3, 1.0)]));
+        assert_eq!(rects, vec![
+            Rect::new(0, 20, 600, 800),
+            Rect::new(600, 20, 400, 400),
+            Rect::new(600, 420, 400, 400),
+        ]);
+
+        // A lone client gets the whole area
+        let rects = Tile.arrange(&input(vec![client(1, 1.0)]));
+        assert_eq!(rects, vec![Rect::new(0, 20, 1000, 800)]);
+    }
+
+    #[test]
+    fn test_tile_cfacts_and_gaps() {
+        let mut inp = input(vec![client(1, 1.0), client(2, 3.0), client(3, 1.0)]);
+        let rects = Tile.arrange(&inp);
+        assert_eq!(rects[1], Rect::new(600, 20, 400, 600));
+        assert_eq!(rects[2], Rect::new(600, 620, 400, 200));
+
+        inp.gaps = Gaps { oh: 10, ov: 10, ih: 10, iv: 10 };
+        let rects = Tile.arrange(&inp);
+        assert_eq!(rects[0], Rect::new(10, 30, 582, 780));
+        assert_eq!(rects[1].x, 602);
+        assert_eq!(rects[1].w, 388);
+        assert_eq!(rects[2].y, rects[1].y + rects[1].h + 10);
+        assert_eq!(rects[2].y + rects[2].h, 810);
+    }
+
+    #[test]
+    fn test_monitor_gaps() {
+        let mut mon = Monitor::default();
+        mon.set_gaps(10, 10, 20, 20);
+        assert_eq!(Gaps::for_monitor(&mon, 3, false), Gaps::default());
+
+        mon.gaps_enabled = true;
+        assert_eq!(Gaps::for_monitor(&mon, 3, true), Gaps { oh: 20, ov: 20, ih: 10, iv: 10 });
+        let mut inp = input(vec![client(1, 1.0)]);
+        inp.gaps = Gaps::for_monitor(&mon, 1, false);
+        assert_eq!(Tile.arrange(&inp), vec![Rect::new(20, 40, 960, 760)]);
+
+        // Smart gaps leave a lone client edge to edge
+        inp.gaps = Gaps::for_monitor(&mon, 1, true);
+        assert_eq!(inp.gaps, Gaps { oh: 0, ov: 0, ih: 10, iv: 10 });
+        assert_eq!(Tile.arrange(&inp), vec![Rect::new(0, 20, 1000, 800)]);
+    }
+
+    #[test]
+    fn test_borders_are_subtracted() {
+        let mut c = client(1, 1.0);
+        c.bw = 5;
+        let rects = Monocle.arrange(&input(vec![c, c]));
+        assert_eq!(rects, vec![Rect::new(0, 20, 990, 790); 2]);
+    }
+
+    #[test]
+    fn test_dynamic_symbols() {
+        let inp = input(vec![client(1, 1.0), client(2, 1.0), client(3, 1.0)]);
+        assert_eq!(Monocle.symbol_for(&inp), "[3]");
+        assert_eq!(Deck.symbol_for(&inp), "[D2]");
+        assert_eq!(Tile.symbol_for(&inp), "[]=");
+    }
+
+    #[test]
+    fn test_deck_overlaps_stack() {
+        let rects = Deck.arrange(&input(vec![client(1, 1.0), client(2, 1.0), client(3, 1.0)]));
+        assert_eq!(rects[0], Rect::new(0, 20, 600, 800));
+        assert_eq!(rects[1], Rect::new(600, 20, 400, 800));
+        assert_eq!(rects[1], rects[2]);
+    }
+
+    #[test]
+    fn test_fibonacci() {
+        let clients = vec![client(1, 1.0), client(2, 1.0), client(3, 1.0)];
+        let rects = Spiral.arrange(&input(clients.clone()));
+        assert_eq!(rects, vec![
+            Rect::new(0, 20, 600, 800),
+            Rect::new(600, 20, 400, 400),
+            Rect::new(600, 420, 400, 400),
+        ]);
+
+        let rects = Dwindle.arrange(&input(clients[..1].to_vec()));
+        assert_eq!(rects, vec![Rect::new(0, 20, 1000, 800)]);
+
+        // From the fourth client on, spiral turns inwards while dwindle keeps
+        // going right and down
+        let clients = vec![client(1, 1.0), client(2, 1.0), client(3, 1.0), client(4, 1.0)];
+        let spiral = Spiral.arrange(&input(clients.clone()));
+        assert_eq!(spiral[2..], [Rect::new(800, 420, 200, 400), Rect::new(600, 420, 200, 400)]);
+        let dwindle = Dwindle.arrange(&input(clients));
+        assert_eq!(dwindle[2..], [Rect::new(600, 420, 200, 400), Rect::new(800, 420, 200, 400)]);
+        assert_eq!(spiral[..2], dwindle[..2]);
+    }
+
+    #[test]
+    fn test_nearest_in_direction() {
+        // Master on the left, two stacked clients on the right, a floating one far below
+        let windows = [
+            (1, Rect::new(0, 0, 500, 800)),
+            (2, Rect::new(500, 0, 500, 400)),
+            (3, Rect::new(500, 400, 500, 400)),
+            (4, Rect::new(100, 900, 100, 100)),
+        ];
+        let others = |id| windows.iter().filter(|w| w.0 != id).cloned().collect::<Vec<_>>();
+
+        assert_eq!(nearest_in_direction(windows[0].1, &others(1), Direction::Right), Some(2));
+        assert_eq!(nearest_in_direction(windows[2].1, &others(3), Direction::Left), Some(1));
+        assert_eq!(nearest_in_direction(windows[1].1, &others(2), Direction::Down), Some(3));
+        assert_eq!(nearest_in_direction(windows[2].1, &others(3), Direction::Up), Some(2));
+        assert_eq!(nearest_in_direction(windows[0].1, &others(1), Direction::Left), None);
+        // Only the floating window shares columns with the master below it
+        assert_eq!(nearest_in_direction(windows[0].1, &others(1), Direction::Down), Some(4));
+    }
+
+    #[test]
+    fn test_layout_registry() {
+        for (name, _) in LAYOUTS {
+            assert_eq!(layout_by_name(name).unwrap().name(), *name);
         }
+        assert!(layout_by_name("nope").is_none());
+        assert!(layout_by_name("float").unwrap().is_floating());
     }
 }
@@ -1,6 +1,7 @@
 use std::time::{Duration, Instant};
 use x11::xlib;
 use std::fs::{File, OpenOptions};
+use std::os::unix::process::CommandExt;
 use std::process::Command;
 use std::sync::mpsc::channel;
 use std::thread;
@@ -16,6 +17,7 @@ mod colour;
 mod state;
 mod config;
 mod layouts;
+mod tree;
 mod actions;
 mod events;
 mod bar;
@@ -23,6 +25,12 @@ mod error;
 mod client;
 mod monitor;
 mod launcher;
+mod process;
+mod mru;
+mod restart;
+mod drag;
+mod dock;
+mod close;
 
 pub use ivec2::*;
 pub use xwrapper::*;
@@ -30,6 +38,7 @@ pub use colour::*;
 pub use state::*;
 pub use config::*;
 pub use layouts::*;
+pub use tree::*;
 pub use actions::*;
 pub use events::*;
 pub use bar::*;
@@ -37,9 +46,20 @@ pub use error::*;
 pub use client::*;
 pub use monitor::*;
 pub use launcher::*;
+pub use process::*;
+pub use mru::*;
+pub use restart::*;
+pub use drag::*;
+pub use close::*;
 
 const TAG_MASK: u32 = (1 << config::TAGS.len()) - 1;
 
+/// Tag bit of scratchpad `i`. These sit above `TAG_MASK`, so no normal tag view
+/// ever includes a scratchpad.
+fn scratch_tag(i: usize) -> u32 {
+    1 << (config::TAGS.len() + i)
+}
+
 #[derive(PartialEq, Copy, Clone)]
 enum CursorType {
     Normal,
@@ -49,7 +69,9 @@ enum CursorType {
 }
 
 impl Gmux {
-    fn apply_rules(&self, client: &mut Client) {
+    /// Applies the first matching rule to a new client. Returns the rule's attach
+    /// mode, if it overrides the monitor's.
+    fn apply_rules(&self, client: &mut Client) -> Option<AttachMode> {
         let rules = config::rules();
         
         // Get window properties
@@ -89,8 +111,12 @@ impl Gmux {
             
             if class_matches && instance_matches && title_matches {
                 // Apply the rule
-                client.tags = rule.tags;
+                if rule.tags & TAG_MASK != 0 {
+                    client.tags = rule.tags & TAG_MASK;
+                }
                 client.is_floating = rule.is_floating;
+                client.is_terminal = rule.is_terminal;
+                client.no_swallow = rule.no_swallow;
                 
                 // Apply monitor assignment if specified
                 if rule.monitor >= 0 && (rule.monitor as usize) < self.mons.len() {
@@ -98,12 +124,19 @@ impl Gmux {
                 } else {
                     client.monitor_idx = self.selected_monitor;
                 }
+
+                if let Some(i) = rule.scratchpad.filter(|&i| i < SCRATCHPADS.len()) {
+                    client.tags = scratch_tag(i);
+                    client.is_floating = true;
+                    client.centre_on(&self.mons[client.monitor_idx], &SCRATCHPADS[i]);
+                }
                 
                 log::info!("Applied rule to window '{}' (class: {:?}, instance: {:?}): tags={:b}, floating={}, monitor={}", 
                           client.name, class, instance, client.tags, client.is_floating, client.monitor_idx);
-                break; // Use first matching rule
+                return rule.attach; // Use first matching rule
             }
         }
+        None
     }
 
     fn process_error(&mut self, error: GmuxError) {
@@ -120,6 +153,15 @@ impl Gmux {
         self.xwrapper.sync(false);
         while self.running != 0 {
             let now = Instant::now();
+            if self.quit_deadline.is_some_and(|deadline| self.clients.is_empty() || now >= deadline) {
+                break;
+            }
+            self.check_pending_close();
+            // Don't block on the next event past a running timeout, or it could pass unnoticed
+            let deadline = self.pending_close.values().map(|p| p.deadline).chain(self.quit_deadline).min();
+            if deadline.is_some_and(|d| !self.xwrapper.wait_for_event(d.saturating_duration_since(now))) {
+                continue;
+            }
             bar_acc += now.duration_since(last_frame);
             last_frame = now;
 
@@ -136,18 +178,24 @@ impl Gmux {
             if let Some(ev) = self.xwrapper.next_event() {
                 match ev {
                     xwrapper::Event::KeyPress(kev) => {
-                        if let BarState::Launcher { .. } = self.bar_state {
+                        if self.mru_cycle.is_some() {
+                            self.handle_mru_keypress(&kev);
+                        } else if let BarState::Launcher { .. } = self.bar_state {
                             self.handle_launcher_keypress(&kev);
                         } else if let Some(action) = events::parse_key_press(self, &kev) {
                             action.execute(self)
                         }
                     }
+                    xwrapper::Event::KeyRelease(kev) if self.mru_cycle.is_some() => {
+                        self.handle_mru_keyrelease(&kev);
+                    }
                     xwrapper::Event::ButtonPress(mut bev) => unsafe { events::button_press(self, &mut bev) },
                     xwrapper::Event::MotionNotify(mut mev) => unsafe { events::motion_notify(self, &mut mev) },
                     xwrapper::Event::MapRequest(mut mrev) => unsafe { events::map_request(self, &mut mrev) },
                     xwrapper::Event::UnmapNotify(mut urev) => unsafe { events::unmap_notify(self, &mut urev) },
                     xwrapper::Event::DestroyNotify(mut drev) => unsafe { events::destroy_notify(self, &mut drev) },
                     xwrapper::Event::EnterNotify(mut erev) => unsafe { events::enter_notify(self, &mut erev) },
+                    xwrapper::Event::FocusIn(mut fiev) => events::focus_in(self, &mut fiev),
                     xwrapper::Event::PropertyNotify(mut prev) => unsafe { events::property_notify(self, &mut prev) },
 
                     // ADDED: Handle screen configuration changes (e.g., wake from sleep)
@@ -190,7 +238,7 @@ impl Gmux {
                 if c.is_visible_on(&self.mons[mon_idx]) {
                     self.xwrapper.move_window(c.win, c.x, c.y);
                     let client_mon = &self.mons[c.monitor_idx];
-                    if client_mon.lt[client_mon.selected_lt as usize].arrange.is_none()
+                    if client_mon.layout().is_floating()
                         || c.is_floating && !c.is_fullscreen
                     {
                         self.resize(handle, c.x, c.y, c.w, c.h);
@@ -219,12 +267,23 @@ impl Gmux {
         if !destroyed {
             if let Some(client) = self.clients.get(&handle) {
                 self.xwrapper.unmanage_window(client.win);
+                self.xwrapper.set_border_width(client.win, client.orig_bw.max(0) as u32);
+                // Iconic windows stay iconic so the next window manager adopts them
+                if !client.is_hidden {
+                    self.xwrapper.set_wm_state(client.win, crate::xwrapper::WITHDRAWN_STATE);
+                }
             }
         }
+
+        if self.swallowed.contains_key(&handle) {
+            self.unswallow(handle);
+        }
+        self.forget_pending_close(handle);
         self.clients.remove(&handle);
 
         let mon = &mut self.mons[mon_idx];
         mon.stack.retain(|&h| h != handle);
+        mon.forget(handle);
 
         let new_sel = if mon.sel == Some(handle) {
             mon.stack.first().cloned()
@@ -238,7 +297,75 @@ impl Gmux {
     }
 
 
-    unsafe fn manage(&mut self, w: xlib::Window, wa: &mut xlib::XWindowAttributes) {
+    /// Finds the managed terminal that `client` was started from, if it should
+    /// swallow it.
+    fn find_terminal(&self, client: &Client) -> Option<ClientHandle> {
+        if client.is_terminal || client.no_swallow || client.pid == 0
+            || (client.is_floating && !SWALLOW_FLOATING)
+        {
+            return None;
+        }
+        let terminals: Vec<(ClientHandle, u32)> = self.clients.values()
+            .filter(|c| c.is_terminal && c.pid != 0 && !c.is_hidden)
+            .map(|c| (c.handle(), c.pid))
+            .collect();
+        nearest_ancestor(&ProcFs, client.pid, &terminals)
+    }
+
+    /// Hides the terminal and puts `client` in its place: same monitor, tags,
+    /// geometry and stack slot.
+    fn swallow(&mut self, term_handle: ClientHandle, mut client: Client) {
+        let term = match self.clients.remove(&term_handle) {
+            Some(t) => t,
+            None => return,
+        };
+        let handle = client.handle();
+        client.monitor_idx = term.monitor_idx;
+        client.tags = term.tags;
+        client.is_floating = term.is_floating;
+        client.cfact = term.cfact;
+        client.x = term.x;
+        client.y = term.y;
+        client.w = term.w;
+        client.h = term.h;
+
+        self.mons[term.monitor_idx].replace_client(term_handle, handle);
+
+        log::info!("'{}' swallowed terminal '{}'", client.name, term.name);
+        self.xwrapper.unmap_window(term.win);
+        self.swallowed.insert(handle, term);
+        self.clients.insert(handle, client);
+    }
+
+    /// Brings back the terminal swallowed by `handle` in the same place.
+    fn unswallow(&mut self, handle: ClientHandle) {
+        let (mut term, client) = match (self.swallowed.remove(&handle), self.clients.get(&handle)) {
+            (Some(t), Some(c)) => (t, c),
+            _ => return,
+        };
+        let term_handle = term.handle();
+        term.monitor_idx = client.monitor_idx;
+        term.tags = client.tags;
+        if client.is_fullscreen {
+            term.is_floating = client._old_state;
+            (term.x, term.y, term.w, term.h) = (client.oldx, client.oldy, client.oldw, client.oldh);
+        } else {
+            term.is_floating = client.is_floating;
+            (term.x, term.y, term.w, term.h) = (client.x, client.y, client.w, client.h);
+        }
+
+        self.mons[term.monitor_idx].replace_client(handle, term_handle);
+
+        self.xwrapper.map_window(term.win);
+        self.clients.insert(term_handle, term);
+    }
+
+    /// Starts managing a window. An `iconic` window is kept hidden until it is activated.
+    unsafe fn manage(&mut self, w: xlib::Window, wa: &mut xlib::XWindowAttributes, iconic: bool) {
+        if self.is_dock(Window(w)) {
+            self.add_dock(Window(w));
+            return;
+        }
         let handle = ClientHandle::from(Window(w));
         let mut client = Client {
             win: Window(w),
@@ -262,14 +389,23 @@ impl Gmux {
             min_width: 0,
             min_height: 0,
             bw: BORDER_PX,
+            cfact: 1.0,
             _oldbw: wa.border_width,
+            orig_bw: wa.border_width,
             tags: 0,
             is_fixed: false,
             is_floating: false,
             is_urgent: false,
-            _never_focus: false,
+            urgent_since: None,
+            never_focus: false,
             _old_state: false,
             is_fullscreen: false,
+            is_sticky: false,
+            is_above: false,
+            is_hidden: iconic,
+            pid: 0,
+            is_terminal: false,
+            no_swallow: false,
                 monitor_idx: self.selected_monitor,
         };
 
@@ -277,6 +413,7 @@ impl Gmux {
             if let Some(name) = self.xwrapper.get_window_title(client.win) {
             client.name = name;
         }
+        client.pid = self.xwrapper.get_window_pid(client.win).unwrap_or(0);
 
         // 2. Handle transient windows
             let is_transient = if let Some(parent_win) = self.xwrapper.get_transient_for_hint(client.win) {
@@ -293,13 +430,20 @@ impl Gmux {
             false
         };
 
+        let mut attach = None;
         if !is_transient {
             // First set default tags and monitor
-            client.tags = self.mons[self.selected_monitor].tagset[self.mons[self.selected_monitor].selected_tags as usize];
+            client.tags = self.mons[self.selected_monitor].tagset[self.mons[self.selected_monitor].selected_tags as usize] & TAG_MASK;
             client.monitor_idx = self.selected_monitor;
             
             // Then apply rules which may override the defaults
-            self.apply_rules(&mut client);
+            attach = self.apply_rules(&mut client);
+
+            // A scratchpad was summoned by `toggle_scratch`, so show it right away
+            if client.tags & !TAG_MASK != 0 {
+                let mon = &mut self.mons[client.monitor_idx];
+                mon.tagset[mon.selected_tags as usize] |= client.tags;
+            }
         }
 
         // 3. Process size hints
@@ -335,12 +479,21 @@ impl Gmux {
             }
         }
 
-        let mon_idx = client.monitor_idx;
-        self.clients.insert(handle, client);
-        self.mons[mon_idx].stack.insert(0, handle);
+        let terminal = if is_transient { None } else { self.find_terminal(&client) };
+        if let Some(term) = terminal {
+            self.swallow(term, client);
+        } else {
+            let mon_idx = client.monitor_idx;
+            self.clients.insert(handle, client);
+            let tiled = self.tiled_visible(mon_idx);
+            let mon = &mut self.mons[mon_idx];
+            let mode = attach.unwrap_or(mon.attach);
+            mon.attach(handle, mode, &tiled);
+        }
 
         // Check for existing window state properties (like fullscreen)
         self.update_window_state_properties(handle);
+        self.update_wm_hints(handle);
 
         self.arrange(Some(self.selected_monitor));
         if let Some(sel_client) = self.clients.get(&handle) {
@@ -349,14 +502,18 @@ impl Gmux {
                 xlib::EnterWindowMask | xlib::FocusChangeMask | xlib::PropertyChangeMask,
             );
 
-            self.xwrapper.map_window(sel_client.win);
+            if sel_client.is_hidden {
+                self.xwrapper.set_wm_state(sel_client.win, crate::xwrapper::ICONIC_STATE);
+            } else {
+                self.xwrapper.set_wm_state(sel_client.win, crate::xwrapper::NORMAL_STATE);
+                self.xwrapper.map_window(sel_client.win);
+            }
         }
         
         // Only focus the new client if it's visible on the current tags
         // Otherwise, it should be marked as urgent when it requests focus later
         if let Some(client) = self.clients.get(&handle) {
-            let current_tags = self.mons[self.selected_monitor].tagset[self.mons[self.selected_monitor].selected_tags as usize];
-            if (client.tags & current_tags) != 0 {
+            if client.is_visible_on(&self.mons[self.selected_monitor]) {
                 self.focus(Some(handle));
             }
         }
@@ -368,8 +525,11 @@ impl Gmux {
         if let Some(client) = self.clients.get(&handle) {
             let net_wm_state = self.xwrapper.atoms.get(crate::xwrapper::Atom::Net(crate::xwrapper::Net::WMState));
             let net_wm_fullscreen = self.xwrapper.atoms.get(crate::xwrapper::Atom::Net(crate::xwrapper::Net::WMFullscreen));
+            let net_wm_sticky = self.xwrapper.atoms.get(crate::xwrapper::Atom::Net(crate::xwrapper::Net::WMSticky));
+            let mut fullscreen = false;
+            let mut sticky = false;
             
-            // Check if window has _NET_WM_STATE_FULLSCREEN set
+            // Check if window has _NET_WM_STATE_FULLSCREEN or _NET_WM_STATE_STICKY set
             unsafe {
                 let mut actual_type: xlib::Atom = 0;
                 let mut actual_format: c_int = 0;
@@ -396,15 +556,23 @@ impl Gmux {
                     let atoms = std::slice::from_raw_parts(prop as *const xlib::Atom, nitems as usize);
                     for &atom in atoms {
                         if atom == net_wm_fullscreen {
-                            log::info!("Window already has fullscreen state, applying fullscreen");
-                            // Found fullscreen atom - this window is already fullscreen
-                            self.setfullscreen(handle, true);
-                            break;
+                            fullscreen = true;
+                        } else if atom == net_wm_sticky {
+                            sticky = true;
                         }
                     }
                     xlib::XFree(prop as *mut _);
                 }
             }
+
+            if sticky {
+                log::info!("Window already has sticky state, applying sticky");
+                self.set_sticky(handle, true);
+            }
+            if fullscreen {
+                log::info!("Window already has fullscreen state, applying fullscreen");
+                self.setfullscreen(handle, true);
+            }
         }
     }
 
@@ -417,14 +585,28 @@ impl Gmux {
         }
     }
 
+    /// Manages the windows that already exist when gmux starts, like dwm's scan.
+    /// Transients go second so that their parents are managed by then.
     fn scan(&mut self) {
-        if let Ok((_, _, wins)) = self.xwrapper.query_tree(self.root) {
+        let wins = match self.xwrapper.query_tree(self.root) {
+            Ok((_, _, wins)) => wins,
+            Err(_) => return,
+        };
+        for transients in [false, true] {
             for &win in &wins {
-                if let Ok(_wa) = self.xwrapper.get_window_attributes(win) {
-                    if self.xwrapper.get_transient_for_hint(win).is_some() {
+                let mut wa = match self.xwrapper.get_window_attributes(win) {
+                    Ok(wa) => wa,
+                    Err(_) => continue,
+                };
+                if wa.override_redirect != 0
+                    || self.xwrapper.get_transient_for_hint(win).is_some() != transients
+                    || self.window_to_client_handle(win.0).is_some()
+                {
                     continue;
                 }
-                // Potentially manage the window here if it's not already managed
+                let iconic = self.xwrapper.get_wm_state(win) == Some(crate::xwrapper::ICONIC_STATE);
+                if wa.map_state == xlib::IsViewable || iconic {
+                    unsafe { self.manage(win.0, &mut wa, iconic) };
                 }
             }
         }
@@ -498,11 +680,27 @@ fn main() {
     log::info!("Panic handler installed - panics will be logged");
 
     let (tx, rx) = channel();
-    match Gmux::new(tx, rx) {
+    let restart = match Gmux::new(tx, rx) {
         Ok(mut gmux) => {
             gmux.scan();
+            if std::env::args().any(|a| a == RESTART_ARG) {
+                gmux.restore_restart_state();
+            }
             gmux.run();
+            gmux.restarting
         }
         Err(e) => panic!("{}", e),
+    };
+
+    // The old instance is dropped by now, releasing the display
+    if restart {
+        let mut args = std::env::args();
+        let program = args.next().unwrap_or_else(|| "gmux".to_string());
+        log::info!("Restarting as {}", program);
+        let err = Command::new(&program)
+            .args(args.filter(|a| a != RESTART_ARG))
+            .arg(RESTART_ARG)
+            .exec();
+        log::error!("Failed to restart gmux: {}", err);
     }
 }
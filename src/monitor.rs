@@ -8,17 +8,102 @@ pub struct Clickable {
     pub action: Action,
 }
 
+/// Space a dock reserves along the screen edges, as in _NET_WM_STRUT_PARTIAL.
+/// Widths are from the edges of the whole screen. The ranges say which part of
+/// each edge is covered, inclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Strut {
+    pub left: i32,
+    pub right: i32,
+    pub top: i32,
+    pub bottom: i32,
+    pub left_y: (i32, i32),
+    pub right_y: (i32, i32),
+    pub top_x: (i32, i32),
+    pub bottom_x: (i32, i32),
+}
+
+impl Strut {
+    /// Reads the 12 values of _NET_WM_STRUT_PARTIAL, or the 4 of _NET_WM_STRUT,
+    /// which cover the full length of each edge.
+    pub fn from_property(values: &[i64]) -> Option<Self> {
+        let v: Vec<i32> = values.iter().map(|&v| v as i32).collect();
+        let full = (0, i32::MAX);
+        match v[..] {
+            [left, right, top, bottom, ly0, ly1, ry0, ry1, tx0, tx1, bx0, bx1] => Some(Strut {
+                left, right, top, bottom,
+                left_y: (ly0, ly1),
+                right_y: (ry0, ry1),
+                top_x: (tx0, tx1),
+                bottom_x: (bx0, bx1),
+            }),
+            [left, right, top, bottom] => Some(Strut {
+                left, right, top, bottom,
+                left_y: full,
+                right_y: full,
+                top_x: full,
+                bottom_x: full,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// The part of a monitor at `area` that is not reserved by any of the struts,
+/// on a screen of `screen_w` by `screen_h`.
+pub fn apply_struts(area: Rect, screen_w: i32, screen_h: i32, struts: &[Strut]) -> Rect {
+    let overlaps = |(start, end): (i32, i32), from: i32, len: i32| start < from + len && end >= from;
+    let (mut left, mut top) = (area.x, area.y);
+    let (mut right, mut bottom) = (area.x + area.w, area.y + area.h);
+    for s in struts {
+        if s.left > 0 && overlaps(s.left_y, area.y, area.h) {
+            left = left.max(s.left.min(area.x + area.w));
+        }
+        if s.right > 0 && overlaps(s.right_y, area.y, area.h) {
+            right = right.min((screen_w - s.right).max(area.x));
+        }
+        if s.top > 0 && overlaps(s.top_x, area.x, area.w) {
+            top = top.max(s.top.min(area.y + area.h));
+        }
+        if s.bottom > 0 && overlaps(s.bottom_x, area.x, area.w) {
+            bottom = bottom.min((screen_h - s.bottom).max(area.y));
+        }
+    }
+    Rect::new(left, top, (right - left).max(0), (bottom - top).max(0))
+}
+
+/// Where a new client is put in the stack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttachMode {
+    /// Becomes the master.
+    Top,
+    Bottom,
+    /// Directly above the focused client.
+    Above,
+    /// Directly below the focused client.
+    Below,
+    /// First in the stack area, leaving the masters in place.
+    Aside,
+}
+
 #[derive(Debug, Clone)]
 pub struct Monitor {
     pub lt_symbol: String,
     pub mfact: f32,
     pub nmaster: i32,
+    pub gaps_enabled: bool,
+    pub gap_ih: i32,
+    pub gap_iv: i32,
+    pub gap_oh: i32,
+    pub gap_ov: i32,
     pub _num: i32,
     pub by: i32,
-    pub _mx: i32,
-    pub _my: i32,
-    pub _mw: i32,
-    pub _mh: i32,
+    /// The monitor's area on the screen.
+    pub mx: i32,
+    pub my: i32,
+    pub mw: i32,
+    pub mh: i32,
+    /// The part of it left for windows, without the bar and dock struts.
     pub wx: i32,
     pub wy: i32,
     pub ww: i32,
@@ -31,8 +116,15 @@ pub struct Monitor {
     pub clickables: Vec<Clickable>,
     pub sel: Option<ClientHandle>,
     pub stack: Vec<ClientHandle>,
+    /// Clients in the order they were focused, most recent first.
+    pub focus_history: Vec<ClientHandle>,
+    /// The master that was last displaced by `Zoom`, swapped back in when the
+    /// master itself is zoomed.
+    pub prev_zoom: Option<ClientHandle>,
+    /// Where new clients go unless their rule says otherwise.
+    pub attach: AttachMode,
     pub bar_window: Window,
-    pub lt: [&'static Layout; 2],
+    pub lt: [Box<dyn Layout>; 2],
 }
 
 impl Monitor {
@@ -47,6 +139,148 @@ impl Monitor {
         self.sel.and_then(|h| clients.get(&h))
     }
 
+    pub fn layout(&self) -> &dyn Layout {
+        self.lt[self.selected_lt as usize].as_ref()
+    }
+
+    /// Switches to the layout registered under `name`, like dwm's setlayout:
+    /// the previous layout moves to the other slot. Instances already in a
+    /// slot are reused, so layouts keep their state such as the manual trees.
+    /// Returns false for an unknown name.
+    pub fn set_layout(&mut self, name: &str) -> bool {
+        let sellt = self.selected_lt as usize;
+        if self.lt[sellt].name() != name {
+            self.selected_lt ^= 1;
+            if self.lt[sellt ^ 1].name() != name {
+                match layouts::layout_by_name(name) {
+                    Some(l) => self.lt[sellt ^ 1] = l,
+                    None => {
+                        self.selected_lt ^= 1;
+                        return false;
+                    }
+                }
+            }
+        }
+        self.lt_symbol = self.layout().symbol().to_string();
+        true
+    }
+
+    pub fn note_focus(&mut self, handle: ClientHandle) {
+        self.forget(handle);
+        self.focus_history.insert(0, handle);
+    }
+
+    pub fn forget(&mut self, handle: ClientHandle) {
+        self.focus_history.retain(|&h| h != handle);
+        if self.prev_zoom == Some(handle) {
+            self.prev_zoom = None;
+        }
+    }
+
+    /// The monitor's clients, most recently focused first. Clients that were never
+    /// focused follow in stack order.
+    pub fn mru_clients(&self) -> Vec<ClientHandle> {
+        let mut clients: Vec<ClientHandle> = self.focus_history.iter()
+            .filter(|h| self.stack.contains(h))
+            .cloned()
+            .collect();
+        clients.extend(self.stack.iter().filter(|h| !self.focus_history.contains(h)));
+        clients
+    }
+
+    /// Puts `new` in the place of `old`: same stack slot, selection, focus
+    /// history and layout position.
+    pub fn replace_client(&mut self, old: ClientHandle, new: ClientHandle) {
+        for h in self.stack.iter_mut().chain(self.focus_history.iter_mut()) {
+            if *h == old {
+                *h = new;
+            }
+        }
+        if self.sel == Some(old) {
+            self.sel = Some(new);
+        }
+        if self.prev_zoom == Some(old) {
+            self.prev_zoom = Some(new);
+        }
+        for lt in self.lt.iter_mut() {
+            lt.replace_client(old, new);
+        }
+    }
+
+    /// Inserts a new client into the stack. `tiled` are the visible tiled clients,
+    /// which decide where the masters end for `Aside`.
+    pub fn attach(&mut self, handle: ClientHandle, mode: AttachMode, tiled: &[ClientHandle]) {
+        let sel_pos = self.sel.and_then(|s| self.stack.iter().position(|h| *h == s));
+        let idx = match mode {
+            AttachMode::Top => 0,
+            AttachMode::Bottom => self.stack.len(),
+            AttachMode::Above => sel_pos.unwrap_or(0),
+            AttachMode::Below => sel_pos.map_or(self.stack.len(), |p| p + 1),
+            AttachMode::Aside => {
+                let nmaster = (self.nmaster.max(0) as usize).min(tiled.len());
+                match nmaster.checked_sub(1).map(|i| tiled[i]) {
+                    Some(last_master) => self.stack.iter().position(|h| *h == last_master).map_or(0, |p| p + 1),
+                    None => 0,
+                }
+            }
+        };
+        self.stack.insert(idx, handle);
+    }
+
+    fn swap_clients(&mut self, a: ClientHandle, b: ClientHandle) {
+        let pa = self.stack.iter().position(|h| *h == a);
+        let pb = self.stack.iter().position(|h| *h == b);
+        if let (Some(pa), Some(pb)) = (pa, pb) {
+            self.stack.swap(pa, pb);
+        }
+    }
+
+    /// Moves `sel` one place up (-1) or down (1) among the `tiled` clients,
+    /// wrapping around at either end.
+    pub fn push(&mut self, tiled: &[ClientHandle], sel: ClientHandle, dir: i32) {
+        let n = tiled.len() as i32;
+        if let Some(i) = tiled.iter().position(|h| *h == sel) {
+            let other = tiled[(i as i32 + dir).rem_euclid(n) as usize];
+            self.swap_clients(sel, other);
+        }
+    }
+
+    /// Shifts every `tiled` client one slot down (1) or up (-1) in the stack,
+    /// the last one wrapping around to the top or the other way round.
+    pub fn rotate(&mut self, tiled: &[ClientHandle], dir: i32) {
+        let slots: Vec<usize> = tiled.iter()
+            .filter_map(|t| self.stack.iter().position(|h| h == t))
+            .collect();
+        let n = slots.len() as i32;
+        for (i, &slot) in slots.iter().enumerate() {
+            self.stack[slot] = tiled[(i as i32 - dir).rem_euclid(n) as usize];
+        }
+    }
+
+    /// dwm's zoomswap: `sel` and the master trade places. Zooming the master
+    /// brings back the client it displaced last, or else the next one. Returns
+    /// the new master.
+    pub fn zoom(&mut self, tiled: &[ClientHandle], sel: ClientHandle) -> Option<ClientHandle> {
+        let master = *tiled.first()?;
+        let target = if sel == master {
+            self.prev_zoom
+                .filter(|p| *p != master && tiled.contains(p))
+                .or_else(|| tiled.get(1).copied())?
+        } else {
+            sel
+        };
+        self.swap_clients(target, master);
+        self.prev_zoom = Some(master);
+        Some(target)
+    }
+
+    pub fn set_gaps(&mut self, ih: i32, iv: i32, oh: i32, ov: i32) {
+        self.gap_ih = ih.max(0);
+        self.gap_iv = iv.max(0);
+        self.gap_oh = oh.max(0);
+        self.gap_ov = ov.max(0);
+    }
+
     pub fn intersect_area(&self, x: i32, y: i32, w: i32, h: i32) -> i32 {
         std::cmp::max(
             0,
@@ -64,12 +298,17 @@ impl Default for Monitor {
             lt_symbol: String::new(),
             mfact: 0.0,
             nmaster: 0,
+            gaps_enabled: false,
+            gap_ih: 0,
+            gap_iv: 0,
+            gap_oh: 0,
+            gap_ov: 0,
             _num: 0,
             by: 0,
-            _mx: 0,
-            _my: 0,
-            _mw: 0,
-            _mh: 0,
+            mx: 0,
+            my: 0,
+            mw: 0,
+            mh: 0,
             wx: 0,
             wy: 0,
             ww: 0,
@@ -82,8 +321,135 @@ impl Default for Monitor {
             clickables: Vec::new(),
             sel: None,
             stack: Vec::new(),
+            focus_history: Vec::new(),
+            prev_zoom: None,
+            attach: AttachMode::Top,
             bar_window: Window(0),
-            lt: [&crate::layouts::LAYOUTS[0], &crate::layouts::LAYOUTS[1]],
+            lt: [Box::new(Tile), Box::new(Floating)],
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handles(ids: &[u64]) -> Vec<ClientHandle> {
+        ids.iter().map(|&id| ClientHandle::from(Window(id))).collect()
+    }
+
+    fn monitor(ids: &[u64]) -> Monitor {
+        Monitor {
+            stack: handles(ids),
+            ..Monitor::default()
+        }
+    }
+
+    #[test]
+    fn test_set_layout_keeps_instances() {
+        let addr = |m: &Monitor| m.layout() as *const dyn Layout as *const () as usize;
+        let mut mon = monitor(&[]);
+        assert!(mon.set_layout("tree"));
+        let tree = addr(&mon);
+        assert!(mon.set_layout("tree"));
+        assert_eq!(addr(&mon), tree);
+
+        // Switching away and back finds the tree in the other slot
+        assert!(mon.set_layout("tile"));
+        assert_eq!(mon.layout().name(), "tile");
+        assert!(mon.set_layout("tree"));
+        assert_eq!(addr(&mon), tree);
+
+        assert!(!mon.set_layout("nonsense"));
+        assert_eq!(addr(&mon), tree);
+        assert_eq!(mon.lt_symbol, mon.layout().symbol());
+    }
+
+    #[test]
+    fn test_apply_struts() {
+        let screen = Rect::new(0, 0, 1920, 1080);
+        let panel = Strut::from_property(&[0, 0, 30, 0]).unwrap();
+        assert_eq!(apply_struts(screen, 1920, 1080, &[panel]), Rect::new(0, 30, 1920, 1050));
+
+        // A bottom panel and a right dock, partial struts covering the left monitor only
+        let left_mon = Rect::new(0, 0, 1920, 1080);
+        let right_mon = Rect::new(1920, 0, 1280, 1024);
+        let bottom = Strut::from_property(&[0, 0, 0, 40, 0, 0, 0, 0, 0, 0, 0, 1919]).unwrap();
+        assert_eq!(apply_struts(left_mon, 3200, 1080, &[bottom]), Rect::new(0, 0, 1920, 1040));
+        assert_eq!(apply_struts(right_mon, 3200, 1080, &[bottom]), right_mon);
+        let dock = Strut::from_property(&[0, 64, 0, 0, 0, 0, 0, 1023, 0, 0, 0, 0]).unwrap();
+        assert_eq!(apply_struts(right_mon, 3200, 1080, &[dock]), Rect::new(1920, 0, 1216, 1024));
+
+        assert_eq!(Strut::from_property(&[1, 2, 3]), None);
+    }
+
+    #[test]
+    fn test_attach() {
+        let h = |id| ClientHandle::from(Window(id));
+        // 9 is floating, 2 is focused
+        let stack = [1, 9, 2, 3];
+        let tiled = handles(&[1, 2, 3]);
+        let attached = |mode, nmaster| {
+            let mut mon = monitor(&stack);
+            mon.sel = Some(h(2));
+            mon.nmaster = nmaster;
+            mon.attach(h(5), mode, &tiled);
+            mon.stack
+        };
+        assert_eq!(attached(AttachMode::Top, 1), handles(&[5, 1, 9, 2, 3]));
+        assert_eq!(attached(AttachMode::Bottom, 1), handles(&[1, 9, 2, 3, 5]));
+        assert_eq!(attached(AttachMode::Above, 1), handles(&[1, 9, 5, 2, 3]));
+        assert_eq!(attached(AttachMode::Below, 1), handles(&[1, 9, 2, 5, 3]));
+        assert_eq!(attached(AttachMode::Aside, 1), handles(&[1, 5, 9, 2, 3]));
+        assert_eq!(attached(AttachMode::Aside, 2), handles(&[1, 9, 2, 5, 3]));
+        assert_eq!(attached(AttachMode::Aside, 5), handles(&[1, 9, 2, 3, 5]));
+        assert_eq!(attached(AttachMode::Aside, 0), handles(&[5, 1, 9, 2, 3]));
+
+        // Without a selection Above and Below fall back to top and bottom
+        let mut mon = monitor(&[1, 2]);
+        mon.attach(h(5), AttachMode::Above, &[]);
+        mon.attach(h(6), AttachMode::Below, &[]);
+        assert_eq!(mon.stack, handles(&[5, 1, 2, 6]));
+    }
+
+    #[test]
+    fn test_push() {
+        // 9 is floating and stays put
+        let mut mon = monitor(&[1, 9, 2, 3]);
+        let tiled = handles(&[1, 2, 3]);
+        let h = |id| ClientHandle::from(Window(id));
+        mon.push(&tiled, h(2), -1);
+        assert_eq!(mon.stack, handles(&[2, 9, 1, 3]));
+
+        let tiled = handles(&[2, 1, 3]);
+        mon.push(&tiled, h(3), 1);
+        assert_eq!(mon.stack, handles(&[3, 9, 1, 2]));
+    }
+
+    #[test]
+    fn test_rotate() {
+        let mut mon = monitor(&[1, 9, 2, 3]);
+        mon.rotate(&handles(&[1, 2, 3]), 1);
+        assert_eq!(mon.stack, handles(&[3, 9, 1, 2]));
+        mon.rotate(&handles(&[3, 1, 2]), -1);
+        assert_eq!(mon.stack, handles(&[1, 9, 2, 3]));
+    }
+
+    #[test]
+    fn test_zoomswap() {
+        let h = |id| ClientHandle::from(Window(id));
+        let mut mon = monitor(&[1, 2, 3]);
+        assert_eq!(mon.zoom(&handles(&[1, 2, 3]), h(3)), Some(h(3)));
+        assert_eq!(mon.stack, handles(&[3, 2, 1]));
+
+        // Zooming the master brings the displaced client back
+        assert_eq!(mon.zoom(&handles(&[3, 2, 1]), h(3)), Some(h(1)));
+        assert_eq!(mon.stack, handles(&[1, 2, 3]));
+
+        // Without a usable previous master the next client is promoted
+        mon.prev_zoom = None;
+        assert_eq!(mon.zoom(&handles(&[1, 2, 3]), h(1)), Some(h(2)));
+        assert_eq!(mon.stack, handles(&[2, 1, 3]));
+        assert_eq!(monitor(&[1]).zoom(&handles(&[1]), h(1)), None);
+    }
+}
@@ -0,0 +1,126 @@
+use x11::{keysym, xlib};
+
+use crate::*;
+
+/// An alt-tab style walk through the selected monitor's focus history. Every
+/// step previews a window by focusing it; the history itself is only updated
+/// once the modifier is released.
+#[derive(Debug, Clone)]
+pub struct MruCycle {
+    pub clients: Vec<ClientHandle>,
+    pub idx: usize,
+}
+
+impl MruCycle {
+    /// Starts on the most recent window, so the first step lands on the one before it.
+    pub fn new(clients: Vec<ClientHandle>) -> Self {
+        MruCycle { clients, idx: 0 }
+    }
+
+    pub fn step(&mut self, dir: i32) -> Option<ClientHandle> {
+        if self.clients.is_empty() {
+            return None;
+        }
+        let n = self.clients.len() as i32;
+        self.idx = ((self.idx as i32 + dir).rem_euclid(n)) as usize;
+        Some(self.clients[self.idx])
+    }
+
+    pub fn current(&self) -> Option<ClientHandle> {
+        self.clients.get(self.idx).copied()
+    }
+}
+
+impl Gmux {
+    pub fn start_mru_cycle(&mut self, dir: i32) {
+        let mon = &self.mons[self.selected_monitor];
+        let clients: Vec<ClientHandle> = mon.mru_clients().into_iter()
+            .filter(|h| self.clients.get(h).is_some_and(|c| c.is_visible_on(mon)))
+            .collect();
+        if clients.len() < 2 {
+            return;
+        }
+        self.mru_cycle = Some(MruCycle::new(clients));
+        self.xwrapper.grab_keyboard(self.root);
+        self.step_mru_cycle(dir);
+    }
+
+    fn step_mru_cycle(&mut self, dir: i32) {
+        let next = self.mru_cycle.as_mut().and_then(|c| c.step(dir));
+        if let Some(handle) = next.filter(|h| self.clients.contains_key(h)) {
+            self.focus(Some(handle));
+            self.restack(self.selected_monitor);
+        }
+    }
+
+    /// Ends the cycle, keeping the previewed window or going back to the original one.
+    fn end_mru_cycle(&mut self, commit: bool) {
+        let cycle = match self.mru_cycle.take() {
+            Some(c) => c,
+            None => return,
+        };
+        self.xwrapper.ungrab_keyboard();
+        let target = if commit { cycle.current() } else { cycle.clients.first().copied() };
+        if target.is_some_and(|h| self.clients.contains_key(&h)) {
+            self.focus(target);
+            self.restack(self.selected_monitor);
+        }
+    }
+
+    pub fn handle_mru_keypress(&mut self, kev: &xlib::XKeyEvent) {
+        let keysym = self.xwrapper.keycode_to_keysym(kev.keycode) as u32;
+        match keysym {
+            keysym::XK_Tab | keysym::XK_ISO_Left_Tab => {
+                let dir = if kev.state & xlib::ShiftMask != 0 { -1 } else { 1 };
+                self.step_mru_cycle(dir);
+            }
+            keysym::XK_Return => self.end_mru_cycle(true),
+            keysym::XK_Escape => self.end_mru_cycle(false),
+            _ => {}
+        }
+    }
+
+    pub fn handle_mru_keyrelease(&mut self, kev: &xlib::XKeyEvent) {
+        let keysym = self.xwrapper.keycode_to_keysym(kev.keycode) as u32;
+        if MRU_RELEASE_KEYS.contains(&keysym) {
+            self.end_mru_cycle(true);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handle(id: u64) -> ClientHandle {
+        ClientHandle::from(Window(id))
+    }
+
+    #[test]
+    fn test_cycle_wraps_both_ways() {
+        let mut cycle = MruCycle::new(vec![handle(1), handle(2), handle(3)]);
+        assert_eq!(cycle.step(1), Some(handle(2)));
+        assert_eq!(cycle.step(1), Some(handle(3)));
+        assert_eq!(cycle.step(1), Some(handle(1)));
+        assert_eq!(cycle.step(-1), Some(handle(3)));
+        assert_eq!(cycle.current(), Some(handle(3)));
+
+        assert_eq!(MruCycle::new(Vec::new()).step(1), None);
+    }
+
+    #[test]
+    fn test_focus_history() {
+        let mut mon = Monitor {
+            stack: vec![handle(1), handle(2), handle(3)],
+            ..Monitor::default()
+        };
+        mon.note_focus(handle(2));
+        mon.note_focus(handle(3));
+        mon.note_focus(handle(2));
+        // Never focused windows come last, in stack order
+        assert_eq!(mon.mru_clients(), vec![handle(2), handle(3), handle(1)]);
+
+        mon.forget(handle(3));
+        assert_eq!(mon.focus_history, vec![handle(2)]);
+    }
+}
@@ -0,0 +1,175 @@
+use std::fs;
+use std::path::PathBuf;
+
+/// Process names that count as shells when looking for a terminal's working directory.
+const SHELLS: &[&str] = &["sh", "bash", "zsh", "fish", "dash", "ksh", "mksh", "tcsh", "nu", "xonsh"];
+
+/// Relations between processes, as needed for window swallowing and for
+/// spawning terminals in the working directory of another one.
+pub trait ProcessTree {
+    /// Parent of `pid`, or `None` if it does not exist.
+    fn parent(&self, pid: u32) -> Option<u32>;
+
+    fn children(&self, pid: u32) -> Vec<u32>;
+
+    /// Command name of `pid`, as in `/proc/<pid>/comm`.
+    fn name(&self, pid: u32) -> Option<String>;
+
+    /// `pid` followed by its parent, grandparent and so on up to init.
+    fn ancestors(&self, pid: u32) -> Vec<u32> {
+        let mut chain = vec![pid];
+        let mut pid = pid;
+        // Bounded so a corrupt table can not loop forever
+        while chain.len() < 256 {
+            match self.parent(pid) {
+                Some(ppid) if ppid != 0 && !chain.contains(&ppid) => {
+                    chain.push(ppid);
+                    pid = ppid;
+                }
+                _ => break,
+            }
+        }
+        chain
+    }
+
+    /// The most deeply nested shell below `pid`, following the newest child at
+    /// each level. For a terminal this is the shell the user is typing into,
+    /// even when it was started from another shell.
+    fn deepest_shell(&self, pid: u32) -> Option<u32> {
+        let mut shell = None;
+        let mut pid = pid;
+        for _ in 0..256 {
+            match self.children(pid).into_iter().max() {
+                Some(child) => pid = child,
+                None => break,
+            }
+            if self.name(pid).is_some_and(|n| SHELLS.contains(&n.as_str())) {
+                shell = Some(pid);
+            }
+        }
+        shell
+    }
+}
+
+/// Picks the candidate whose process is the closest ancestor of `pid`, so a
+/// program started from a nested terminal is matched to the innermost one.
+pub fn nearest_ancestor<T: Copy>(tree: &dyn ProcessTree, pid: u32, candidates: &[(T, u32)]) -> Option<T> {
+    tree.ancestors(pid).into_iter()
+        .find_map(|p| candidates.iter().find(|(_, cpid)| *cpid == p))
+        .map(|(t, _)| *t)
+}
+
+/// The live process tree from `/proc`.
+pub struct ProcFs;
+
+impl ProcFs {
+    pub fn cwd(&self, pid: u32) -> Option<PathBuf> {
+        fs::read_link(format!("/proc/{}/cwd", pid)).ok()
+    }
+}
+
+impl ProcessTree for ProcFs {
+    fn parent(&self, pid: u32) -> Option<u32> {
+        let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+        parse_ppid(&stat)
+    }
+
+    fn children(&self, pid: u32) -> Vec<u32> {
+        let entries = match fs::read_dir("/proc") {
+            Ok(e) => e,
+            Err(_) => return Vec::new(),
+        };
+        entries
+            .filter_map(|e| e.ok()?.file_name().to_str()?.parse::<u32>().ok())
+            .filter(|&p| self.parent(p) == Some(pid))
+            .collect()
+    }
+
+    fn name(&self, pid: u32) -> Option<String> {
+        let comm = fs::read_to_string(format!("/proc/{}/comm", pid)).ok()?;
+        Some(comm.trim_end().to_string())
+    }
+}
+
+/// Extracts the parent pid from the contents of `/proc/<pid>/stat`. The command
+/// name in the second field is in parentheses and may itself contain spaces and
+/// parentheses, so parsing starts after the last closing one.
+pub fn parse_ppid(stat: &str) -> Option<u32> {
+    let rest = &stat[stat.rfind(')')? + 1..];
+    // Fields after the name: state, ppid, ...
+    rest.split_whitespace().nth(1)?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fake process table of (pid, parent pid, name).
+    struct FakeProcs(Vec<(u32, u32, &'static str)>);
+
+    impl ProcessTree for FakeProcs {
+        fn parent(&self, pid: u32) -> Option<u32> {
+            self.0.iter().find(|p| p.0 == pid).map(|p| p.1)
+        }
+
+        fn children(&self, pid: u32) -> Vec<u32> {
+            self.0.iter().filter(|p| p.1 == pid).map(|p| p.0).collect()
+        }
+
+        fn name(&self, pid: u32) -> Option<String> {
+            self.0.iter().find(|p| p.0 == pid).map(|p| p.2.to_string())
+        }
+    }
+
+    #[test]
+    fn test_parse_ppid() {
+        assert_eq!(parse_ppid("1234 (mpv) S 1200 1234 1200 34816"), Some(1200));
+        assert_eq!(parse_ppid("99 (my (odd) name) R 42 99 42 0"), Some(42));
+        assert_eq!(parse_ppid("99 (truncated"), None);
+        assert_eq!(parse_ppid(""), None);
+    }
+
+    #[test]
+    fn test_ancestors() {
+        let tree = FakeProcs(vec![(10, 1, "alacritty"), (11, 10, "zsh"), (12, 11, "mpv"), (20, 1, "firefox")]);
+        assert_eq!(tree.ancestors(12), vec![12, 11, 10, 1]);
+        assert_eq!(tree.ancestors(99), vec![99]);
+
+        // A loop in the table terminates
+        let looped = FakeProcs(vec![(5, 6, "a"), (6, 5, "b")]);
+        assert_eq!(looped.ancestors(5), vec![5, 6]);
+    }
+
+    #[test]
+    fn test_nearest_ancestor() {
+        // A terminal (11) started from another terminal's shell (10 -> 100)
+        let tree = FakeProcs(vec![
+            (10, 1, "alacritty"),
+            (100, 10, "zsh"),
+            (11, 100, "alacritty"),
+            (110, 11, "zsh"),
+            (111, 110, "mpv"),
+        ]);
+        let terminals = [("outer", 10), ("inner", 11)];
+        assert_eq!(nearest_ancestor(&tree, 111, &terminals), Some("inner"));
+        assert_eq!(nearest_ancestor(&tree, 100, &terminals), Some("outer"));
+        assert_eq!(nearest_ancestor(&tree, 1, &terminals), None);
+    }
+
+    #[test]
+    fn test_deepest_shell() {
+        // alacritty -> zsh -> bash -> nvim, with an older sleep in the background of zsh
+        let tree = FakeProcs(vec![
+            (10, 1, "alacritty"),
+            (11, 10, "zsh"),
+            (12, 11, "sleep"),
+            (13, 11, "bash"),
+            (14, 13, "nvim"),
+        ]);
+        assert_eq!(tree.deepest_shell(10), Some(13));
+        assert_eq!(tree.deepest_shell(14), None);
+
+        let no_shell = FakeProcs(vec![(10, 1, "firefox"), (11, 10, "firefox")]);
+        assert_eq!(no_shell.deepest_shell(10), None);
+    }
+}
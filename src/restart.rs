@@ -0,0 +1,372 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::*;
+
+/// Layout state of a monitor, carried over an in-place restart.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SavedMonitor {
+    pub tagset: [u32; 2],
+    pub selected_tags: u32,
+    pub layouts: [String; 2],
+    /// What `Layout::save_state` gave for each layout, empty if nothing.
+    pub layout_states: [String; 2],
+    pub selected_lt: u32,
+    pub mfact: f32,
+    pub nmaster: i32,
+    pub sel: Option<u64>,
+}
+
+/// Placement of a client, carried over an in-place restart.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SavedClient {
+    pub win: u64,
+    pub monitor: usize,
+    pub tags: u32,
+    pub is_floating: bool,
+    pub x: i32,
+    pub y: i32,
+    pub w: i32,
+    pub h: i32,
+    pub cfact: f32,
+    /// The terminal this client swallowed, if any.
+    pub swallowed: Option<u64>,
+}
+
+/// Everything a restarted gmux needs to put windows back where they were.
+/// Clients are listed monitor by monitor in stack order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SavedState {
+    pub selected_monitor: usize,
+    pub monitors: Vec<SavedMonitor>,
+    pub clients: Vec<SavedClient>,
+}
+
+/// Passed to the re-executed process so that it picks up the saved state.
+pub const RESTART_ARG: &str = "--restart";
+
+/// Where the old process leaves the state for the new one.
+fn state_path() -> PathBuf {
+    DATA_PATH.join("restart-state")
+}
+
+impl SavedState {
+    /// One tab separated record per line.
+    pub fn serialise(&self) -> String {
+        let mut out = format!("selmon\t{}\n", self.selected_monitor);
+        for m in &self.monitors {
+            out += &format!(
+                "mon\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                m.tagset[0], m.tagset[1], m.selected_tags, m.layouts[0], m.layouts[1],
+                m.layout_states[0], m.layout_states[1],
+                m.selected_lt, m.mfact, m.nmaster, m.sel.unwrap_or(0),
+            );
+        }
+        for c in &self.clients {
+            out += &format!(
+                "client\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                c.win, c.monitor, c.tags, c.is_floating as u8, c.x, c.y, c.w, c.h,
+                c.cfact, c.swallowed.unwrap_or(0),
+            );
+        }
+        out
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut state = SavedState { selected_monitor: 0, monitors: Vec::new(), clients: Vec::new() };
+        // 0 stands for no window
+        let win = |f: &str| f.parse::<u64>().ok().map(|w| Some(w).filter(|&w| w != 0));
+        for line in s.lines().filter(|l| !l.is_empty()) {
+            let f: Vec<&str> = line.split('\t').collect();
+            match f[..] {
+                ["selmon", idx] => state.selected_monitor = idx.parse().ok()?,
+                ["mon", t0, t1, seltags, lt0, lt1, lts0, lts1, sellt, mfact, nmaster, sel] => {
+                    state.monitors.push(SavedMonitor {
+                        tagset: [t0.parse().ok()?, t1.parse().ok()?],
+                        selected_tags: seltags.parse().ok()?,
+                        layouts: [lt0.to_string(), lt1.to_string()],
+                        layout_states: [lts0.to_string(), lts1.to_string()],
+                        selected_lt: sellt.parse().ok()?,
+                        mfact: mfact.parse().ok()?,
+                        nmaster: nmaster.parse().ok()?,
+                        sel: win(sel)?,
+                    });
+                }
+                ["client", w, mon, tags, floating, x, y, cw, ch, cfact, swallowed] => {
+                    state.clients.push(SavedClient {
+                        win: w.parse().ok()?,
+                        monitor: mon.parse().ok()?,
+                        tags: tags.parse().ok()?,
+                        is_floating: floating == "1",
+                        x: x.parse().ok()?,
+                        y: y.parse().ok()?,
+                        w: cw.parse().ok()?,
+                        h: ch.parse().ok()?,
+                        cfact: cfact.parse().ok()?,
+                        swallowed: win(swallowed)?,
+                    });
+                }
+                _ => return None,
+            }
+        }
+        Some(state)
+    }
+}
+
+impl Gmux {
+    fn capture_state(&self) -> SavedState {
+        let monitors = self.mons.iter()
+            .map(|m| SavedMonitor {
+                tagset: m.tagset,
+                selected_tags: m.selected_tags,
+                layouts: [m.lt[0].name().to_string(), m.lt[1].name().to_string()],
+                layout_states: [0, 1].map(|i| m.lt[i].save_state().unwrap_or_default()),
+                selected_lt: m.selected_lt,
+                mfact: m.mfact,
+                nmaster: m.nmaster,
+                sel: m.sel.and_then(|h| self.clients.get(&h)).map(|c| c.win.0),
+            })
+            .collect();
+        let clients = self.mons.iter().enumerate()
+            .flat_map(|(i, m)| m.stack.iter().map(move |handle| (i, handle)))
+            .filter_map(|(i, handle)| {
+                let c = self.clients.get(handle)?;
+                // A fullscreen client gets its fullscreen state back from
+                // _NET_WM_STATE, so keep what it returns to afterwards
+                let (is_floating, x, y, w, h) = if c.is_fullscreen {
+                    (c._old_state, c.oldx, c.oldy, c.oldw, c.oldh)
+                } else {
+                    (c.is_floating, c.x, c.y, c.w, c.h)
+                };
+                Some(SavedClient {
+                    win: c.win.0,
+                    monitor: i,
+                    tags: c.tags,
+                    is_floating,
+                    x,
+                    y,
+                    w,
+                    h,
+                    cfact: c.cfact,
+                    swallowed: self.swallowed.get(handle).map(|t| t.win.0),
+                })
+            })
+            .collect();
+        SavedState { selected_monitor: self.selected_monitor, monitors, clients }
+    }
+
+    /// Publishes the state on the root window as `_GMUX_STATE`, in the same
+    /// format as the restart file, so `xprop -root _GMUX_STATE` shows layouts,
+    /// mfact and the cfact of every client.
+    pub fn publish_state(&self) {
+        let state = self.capture_state().serialise();
+        self.xwrapper.set_utf8_strings(self.root, self.xwrapper.atoms.gmux_state, &[&state]);
+    }
+
+    /// Writes the state for the next process. Swallowed terminals are marked
+    /// iconic so that `scan` adopts them without mapping them.
+    pub fn save_restart_state(&self) -> io::Result<()> {
+        for term in self.swallowed.values() {
+            self.xwrapper.set_wm_state(term.win, ICONIC_STATE);
+        }
+        fs::write(state_path(), self.capture_state().serialise())
+    }
+
+    /// Puts the windows adopted by `scan` back the way the previous process had
+    /// them, if it left its state behind. Only called when started with
+    /// `RESTART_ARG`.
+    pub fn restore_restart_state(&mut self) {
+        let path = state_path();
+        let saved = match fs::read_to_string(&path) {
+            Ok(s) => s,
+            Err(_) => return,
+        };
+        let _ = fs::remove_file(&path);
+        let saved = match SavedState::parse(&saved) {
+            Some(s) => s,
+            None => {
+                log::warn!("Ignoring malformed restart state");
+                return;
+            }
+        };
+        let handle_of = |win: u64| ClientHandle::from(Window(win));
+
+        for (mon, sm) in self.mons.iter_mut().zip(&saved.monitors) {
+            mon.tagset = sm.tagset;
+            mon.selected_tags = sm.selected_tags & 1;
+            for ((lt, name), state) in mon.lt.iter_mut().zip(&sm.layouts).zip(&sm.layout_states) {
+                if lt.name() != name {
+                    match layouts::layout_by_name(name) {
+                        Some(l) => *lt = l,
+                        None => continue,
+                    }
+                }
+                if !state.is_empty() {
+                    lt.restore_state(state);
+                }
+            }
+            mon.selected_lt = sm.selected_lt & 1;
+            mon.lt_symbol = mon.layout().symbol().to_string();
+            mon.mfact = sm.mfact;
+            mon.nmaster = sm.nmaster;
+        }
+
+        let restored: Vec<ClientHandle> = saved.clients.iter()
+            .map(|sc| handle_of(sc.win))
+            .filter(|h| self.clients.contains_key(h))
+            .collect();
+        for mon in self.mons.iter_mut() {
+            mon.stack.retain(|h| !restored.contains(h));
+        }
+        // Saved clients first, in their old order, then anything new
+        let mut stacks: Vec<Vec<ClientHandle>> = vec![Vec::new(); self.mons.len()];
+        for sc in &saved.clients {
+            let handle = handle_of(sc.win);
+            let c = match self.clients.get_mut(&handle) {
+                Some(c) => c,
+                None => continue,
+            };
+            c.monitor_idx = if sc.monitor < stacks.len() { sc.monitor } else { 0 };
+            c.tags = sc.tags;
+            c.cfact = sc.cfact;
+            if c.is_fullscreen {
+                c._old_state = sc.is_floating;
+                (c.oldx, c.oldy, c.oldw, c.oldh) = (sc.x, sc.y, sc.w, sc.h);
+            } else {
+                c.is_floating = sc.is_floating;
+                (c.x, c.y, c.w, c.h) = (sc.x, sc.y, sc.w, sc.h);
+            }
+            stacks[c.monitor_idx].push(handle);
+        }
+        for (mon, mut stack) in self.mons.iter_mut().zip(stacks) {
+            stack.append(&mut mon.stack);
+            mon.stack = stack;
+        }
+
+        for sc in &saved.clients {
+            let (handle, term_handle) = match sc.swallowed {
+                Some(t) => (handle_of(sc.win), handle_of(t)),
+                None => continue,
+            };
+            if !self.clients.contains_key(&handle) {
+                continue;
+            }
+            let mut term = match self.clients.remove(&term_handle) {
+                Some(t) => t,
+                None => continue,
+            };
+            let mon = &mut self.mons[term.monitor_idx];
+            mon.stack.retain(|&h| h != term_handle);
+            mon.forget(term_handle);
+            term.is_hidden = false;
+            // Adopted as viewable if something mapped it in between
+            self.xwrapper.unmap_window(term.win);
+            self.xwrapper.set_wm_state(term.win, NORMAL_STATE);
+            self.swallowed.insert(handle, term);
+        }
+
+        for (mon, sm) in self.mons.iter_mut().zip(&saved.monitors) {
+            mon.sel = sm.sel.map(handle_of).filter(|h| mon.stack.contains(h));
+        }
+        if saved.selected_monitor < self.mons.len() {
+            self.selected_monitor = saved.selected_monitor;
+        }
+        self.arrange(None);
+        let sel = self.mons[self.selected_monitor].sel;
+        self.focus(sel);
+        log::info!("Restored {} clients after restart", restored.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let state = SavedState {
+            selected_monitor: 1,
+            monitors: vec![
+                SavedMonitor {
+                    tagset: [1, 6],
+                    selected_tags: 1,
+                    layouts: ["tile".to_string(), "float".to_string()],
+                    layout_states: [String::new(), String::new()],
+                    selected_lt: 0,
+                    mfact: 0.55,
+                    nmaster: 1,
+                    sel: Some(0x1200003),
+                },
+                SavedMonitor {
+                    tagset: [1 << 5, 1],
+                    selected_tags: 0,
+                    layouts: ["tree".to_string(), "monocle".to_string()],
+                    layout_states: ["1 ( h s 1 1 18874371 1 20971521 )".to_string(), String::new()],
+                    selected_lt: 1,
+                    mfact: 0.6,
+                    nmaster: 2,
+                    sel: None,
+                },
+            ],
+            clients: vec![
+                SavedClient {
+                    win: 0x1200003,
+                    monitor: 0,
+                    tags: 1,
+                    is_floating: false,
+                    x: 10,
+                    y: 30,
+                    w: 940,
+                    h: 1040,
+                    cfact: 1.25,
+                    swallowed: Some(0x1000008),
+                },
+                SavedClient {
+                    win: 0x1400001,
+                    monitor: 1,
+                    tags: 1 << 5,
+                    is_floating: true,
+                    x: -20,
+                    y: 100,
+                    w: 800,
+                    h: 600,
+                    cfact: 1.0,
+                    swallowed: None,
+                },
+            ],
+        };
+        assert_eq!(SavedState::parse(&state.serialise()), Some(state));
+    }
+
+    #[test]
+    fn test_dump_reports_cfact() {
+        let client = SavedClient {
+            win: 0x1200003,
+            monitor: 0,
+            tags: 1,
+            is_floating: false,
+            x: 0,
+            y: 0,
+            w: 100,
+            h: 100,
+            cfact: 1.75,
+            swallowed: None,
+        };
+        let state = SavedState { selected_monitor: 0, monitors: Vec::new(), clients: vec![client] };
+        let dump = state.serialise();
+        let record: Vec<&str> = dump.lines().find(|l| l.starts_with("client\t")).unwrap().split('\t').collect();
+        assert_eq!(record[1], 0x1200003.to_string());
+        assert_eq!(record[9], "1.75");
+    }
+
+    #[test]
+    fn test_parse_rejects_garbage() {
+        assert_eq!(SavedState::parse("mon\t1\t1"), None);
+        assert_eq!(SavedState::parse("client\tx\t0\t1\t0\t0\t0\t1\t1\t1\t0"), None);
+        assert_eq!(
+            SavedState::parse(""),
+            Some(SavedState { selected_monitor: 0, monitors: Vec::new(), clients: Vec::new() })
+        );
+    }
+}
@@ -1,9 +1,10 @@
-use std::os::raw::{c_int, c_uint};
+use std::os::raw::{c_int, c_uint, c_ulong};
 use std::ffi::CString;
 use std::os::raw::c_uchar;
 use std::sync::mpsc::{Sender, Receiver};
 use x11::xlib;
 use std::collections::HashMap;
+use std::time::Instant;
 
 use crate::*;
 
@@ -18,6 +19,12 @@ pub struct Gmux {
     pub lr_padding: u32,
     pub numlock_mask: c_uint,
     pub running: c_int,
+    /// Re-exec gmux once the event loop ends.
+    pub restarting: bool,
+    /// Set while waiting for clients to close before quitting.
+    pub quit_deadline: Option<Instant>,
+    /// Windows that were asked to close and have not yet.
+    pub pending_close: HashMap<ClientHandle, PendingClose>,
     pub cursor: [CursorId; crate::CursorType::Last as usize],
     pub xwrapper: XWrapper,
     pub mons: Vec<Monitor>,
@@ -31,6 +38,11 @@ pub struct Gmux {
     pub command_sender: Sender<GmuxError>,
     pub command_receiver: Receiver<GmuxError>,
     pub clients: HashMap<ClientHandle, Client>,
+    /// Terminals hidden behind a program started from them, keyed by that program.
+    pub swallowed: HashMap<ClientHandle, Client>,
+    pub mru_cycle: Option<MruCycle>,
+    /// Panels and docks, which are not managed, with the struts they reserve.
+    pub docks: HashMap<Window, Option<Strut>>,
 }
 
 impl Gmux {
@@ -149,11 +161,8 @@ impl Gmux {
 
             let new_sel = {
                 let mon = &self.mons[idx];
-                let visible_clients = mon.stack.iter()
-                    .filter_map(|h| self.clients.get(h))
-                    .find(|c| c.is_visible_on(mon));
-
-                if mon.sel.is_some() && visible_clients.is_some() && visible_clients.unwrap().handle() == mon.sel.unwrap() {
+                // Keep the selection while it is still visible
+                if mon.sel.is_some_and(|h| self.clients.get(&h).is_some_and(|c| c.is_visible_on(mon))) {
                     mon.sel
                 } else {
                     mon.stack.iter()
@@ -173,27 +182,160 @@ impl Gmux {
                 self.restack(i);
             }
         }
+        self.update_desktops();
+        self.publish_state();
+    }
+
+    /// Works out the window area of a monitor from its bar and the dock struts,
+    /// and moves the bar into place.
+    pub fn update_work_area(&mut self, mon_idx: usize) {
+        let struts: Vec<Strut> = self.docks.values().flatten().copied().collect();
+        let mon = &mut self.mons[mon_idx];
+        let area = apply_struts(Rect::new(mon.mx, mon.my, mon.mw, mon.mh), self.screen_width, self.screen_height, &struts);
+        (mon.wx, mon.wy, mon.ww, mon.wh) = (area.x, area.y, area.w, area.h);
+        if mon.show_bar {
+            mon.wh -= self.bar_height;
+            if mon.top_bar {
+                mon.by = mon.wy;
+                mon.wy += self.bar_height;
+            } else {
+                mon.by = mon.wy + mon.wh;
+            }
+        } else {
+            mon.by = -self.bar_height;
+        }
+        self.xwrapper.configure_window(mon.bar_window, mon.wx, mon.by, mon.ww, self.bar_height, 0);
+    }
+
+    /// Publishes the current desktop, the work area and every client's desktop,
+    /// with tags standing in for desktops.
+    fn update_desktops(&self) {
+        let mon = &self.mons[self.selected_monitor];
+        let current = match mon.tagset[mon.selected_tags as usize] & TAG_MASK {
+            0 => 0,
+            tags => tags.trailing_zeros(),
+        };
+        self.xwrapper.set_long_property(self.root, self.xwrapper.atoms.get(Atom::Net(Net::CurrentDesktop)),
+            xlib::XA_CARDINAL, &[current as c_ulong]);
+
+        for c in self.clients.values() {
+            self.xwrapper.set_long_property(c.win, self.xwrapper.atoms.get(Atom::Net(Net::WMDesktop)),
+                xlib::XA_CARDINAL, &[c.desktop() as c_ulong]);
+        }
+
+        // The bounding box of the monitors' window areas, the same on every desktop
+        let x = self.mons.iter().map(|m| m.wx).min().unwrap_or(0);
+        let y = self.mons.iter().map(|m| m.wy).min().unwrap_or(0);
+        let r = self.mons.iter().map(|m| m.wx + m.ww).max().unwrap_or(0);
+        let b = self.mons.iter().map(|m| m.wy + m.wh).max().unwrap_or(0);
+        let area = [x as c_ulong, y as c_ulong, (r - x) as c_ulong, (b - y) as c_ulong];
+        let workarea: Vec<c_ulong> = area.iter().copied().cycle().take(4 * TAGS.len()).collect();
+        self.xwrapper.set_long_property(self.root, self.xwrapper.atoms.get(Atom::Net(Net::Workarea)),
+            xlib::XA_CARDINAL, &workarea);
+    }
+
+    /// Publishes _NET_CLIENT_LIST and _NET_CLIENT_LIST_STACKING. The stacking list
+    /// runs bottom to top: hidden clients, then tiled, floating and the selection.
+    fn update_client_list(&self) {
+        let mut list: Vec<c_ulong> = Vec::new();
+        let mut stacking: Vec<c_ulong> = Vec::new();
+        for mon in &self.mons {
+            let clients: Vec<&Client> = mon.stack.iter().filter_map(|h| self.clients.get(h)).collect();
+            list.extend(clients.iter().map(|c| c.win.0));
+            let mut stacked = clients.clone();
+            stacked.sort_by_key(|c| (c.is_visible_on(mon), c.is_floating, mon.sel == Some(c.handle())));
+            stacking.extend(stacked.iter().map(|c| c.win.0));
+        }
+        self.xwrapper.set_long_property(self.root, self.xwrapper.atoms.get(Atom::Net(Net::ClientList)),
+            xlib::XA_WINDOW, &list);
+        self.xwrapper.set_long_property(self.root, self.xwrapper.atoms.get(Atom::Net(Net::ClientListStacking)),
+            xlib::XA_WINDOW, &stacking);
     }
 
     
     pub fn arrange_monitor(&mut self, mon_idx: usize) {
-        if let Some(mon) = self.mons.get(mon_idx) {
-            let layout = mon.lt[mon.selected_lt as usize];
-            if let Some(arrange_fn) = layout.arrange {
-                arrange_fn(self, mon_idx);
+        if SMART_BORDERS {
+            self.update_smart_borders(mon_idx);
+        }
+        let input = self.layout_input(mon_idx);
+        let mon = &mut self.mons[mon_idx];
+        let sellt = mon.selected_lt as usize;
+        let rects = mon.lt[sellt].arrange(&input);
+        mon.lt_symbol = mon.lt[sellt].symbol_for(&input);
+
+        for (client, rect) in input.clients.iter().zip(rects) {
+            self.resize(client.handle, rect.x, rect.y, rect.w, rect.h);
+        }
+    }
+
+    /// Collects the monitor and client state a layout works from.
+    fn layout_input(&self, mon_idx: usize) -> LayoutInput {
+        let mon = &self.mons[mon_idx];
+        let mut visible = 0;
+        let mut clients = Vec::new();
+        for handle in &mon.stack {
+            if let Some(c) = self.clients.get(handle) {
+                if !c.is_visible_on(mon) {
+                    continue;
+                }
+                visible += 1;
+                if !c.is_floating {
+                    clients.push(LayoutClient {
+                        handle: *handle,
+                        bw: c.bw,
+                        cfact: c.cfact,
+                        min_width: c.min_width,
+                        min_height: c.min_height,
+                    });
+                }
+            }
+        }
+
+        let gaps = Gaps::for_monitor(mon, clients.len(), SMART_GAPS);
+
+        LayoutInput {
+            area: Rect::new(mon.wx, mon.wy, mon.ww, mon.wh),
+            clients,
+            visible,
+            focused: mon.sel,
+            tagset: mon.tagset[mon.selected_tags as usize] & TAG_MASK,
+            mfact: mon.mfact,
+            nmaster: mon.nmaster,
+            gaps,
+            min_cell: self.bar_height,
+        }
+    }
+
+    /// Sets the border width of the visible clients on a monitor: tiled clients lose
+    /// their border when they are alone or in monocle, everything else gets it back.
+    /// Fullscreen clients manage their own border width.
+    fn update_smart_borders(&mut self, mon_idx: usize) {
+        let mon = &self.mons[mon_idx];
+        let visible: Vec<ClientHandle> = mon.stack.iter()
+            .filter(|h| self.clients.get(h).is_some_and(|c| c.is_visible_on(mon) && !c.is_fullscreen))
+            .cloned()
+            .collect();
+        let n_tiled = visible.iter()
+            .filter(|h| self.clients.get(h).is_some_and(|c| !c.is_floating))
+            .count();
+        let borderless = n_tiled == 1 || mon.layout().is_monocle();
+
+        for handle in visible {
+            if let Some(c) = self.clients.get_mut(&handle) {
+                c.bw = if borderless && !c.is_floating { 0 } else { BORDER_PX };
             }
         }
     }
 
-    
     pub fn restack(&mut self, mon_idx: usize) {
         self.draw_bar(mon_idx);
+        self.update_client_list();
         let mon = &self.mons[mon_idx];
         if mon.sel.is_none() {
             return;
         }
         if let Some(sel_client) = mon.get_sel_client(&self.clients) {
-            if sel_client.is_floating || mon.lt[mon.selected_lt as usize].arrange.is_none() {
+            if sel_client.is_floating || mon.layout().is_floating() {
                 self.xwrapper.raise_window(sel_client.win);
             }
         }
@@ -201,15 +343,34 @@ impl Gmux {
         let mut windows_to_stack: Vec<Window> = Vec::new();
         windows_to_stack.push(mon.bar_window);
 
+        // Later windows end up on top, so the selected client goes last. This keeps
+        // it visible in layouts where tiled clients overlap (monocle, deck). Clients
+        // the layout wants raised, like the front of a tabbed container, go just below.
+        let raised = mon.layout().raised();
+        let mut raised_wins = Vec::new();
+        let mut sel_win = None;
         for &handle in &mon.stack {
             if let Some(c) = self.clients.get(&handle) {
                 if !c.is_floating && c.is_visible_on(mon) {
-                    windows_to_stack.push(c.win);
+                    if mon.sel == Some(handle) {
+                        sel_win = Some(c.win);
+                    } else if raised.contains(&handle) {
+                        raised_wins.push(c.win);
+                    } else {
+                        windows_to_stack.push(c.win);
+                    }
                 }
             }
         }
+        windows_to_stack.extend(raised_wins);
+        windows_to_stack.extend(sel_win);
 
         self.xwrapper.stack_windows(&windows_to_stack);
+        for &handle in &mon.stack {
+            if let Some(c) = self.clients.get(&handle).filter(|c| c.is_above && c.is_visible_on(mon)) {
+                self.xwrapper.raise_window(c.win);
+            }
+        }
     }
 
     pub fn focus(&mut self, handle: Option<ClientHandle>) {
@@ -221,13 +382,17 @@ impl Gmux {
 
         if let Some(h) = handle {
             // Clear urgent flag when focusing this client
+            if self.clients.get(&h).is_some_and(|c| c.is_urgent) {
+                self.set_urgent(h, false);
+            }
             if let Some(c) = self.clients.get_mut(&h) {
-                if c.is_urgent {
-                    c.is_urgent = false;
-                }
                 if c.monitor_idx != self.selected_monitor {
                     self.selected_monitor = c.monitor_idx;
                 }
+                // Windows passed over by an MRU cycle do not count as used
+                if self.mru_cycle.is_none() {
+                    self.mons[c.monitor_idx].note_focus(h);
+                }
             }
             
             let client_win = if let Some(c) = self.clients.get(&h) {
@@ -252,31 +417,46 @@ impl Gmux {
                     .xwrapper
                     .grab_keys(win, self.numlock_mask, &key_specs);
                 self.xwrapper.set_window_border_color(win, Colour::WindowActive);
-                self
-                    .xwrapper
-                    .set_input_focus(win, xlib::RevertToPointerRoot);
+                self.set_focus(h);
             }
         } else {
             self
                 .xwrapper
                 .set_input_focus(self.root, xlib::RevertToPointerRoot);
-            // XDeleteProperty(dpy, root, netatom[NetActiveWindow]);
+            self.xwrapper.delete_property(self.root, self.xwrapper.atoms.get(Atom::Net(Net::ActiveWindow)));
         }
         self.mons[self.selected_monitor].sel = handle;
         self.draw_bars();
     }
 
+    /// Gives a client the input focus following the ICCCM model, like dwm's
+    /// setfocus. Clients with a false input hint only get `WM_TAKE_FOCUS`.
+    pub fn set_focus(&self, handle: ClientHandle) {
+        let (win, never_focus) = match self.clients.get(&handle) {
+            Some(c) => (c.win, c.never_focus),
+            None => return,
+        };
+        if !never_focus {
+            self.xwrapper.set_input_focus(win, xlib::RevertToPointerRoot);
+            self.xwrapper.set_long_property(self.root, self.xwrapper.atoms.get(Atom::Net(Net::ActiveWindow)),
+                xlib::XA_WINDOW, &[win.0]);
+        }
+        self.xwrapper.send_wm_protocol_event(win, self.xwrapper.atoms.get(Atom::Wm(WM::TakeFocus)));
+    }
+
     pub fn unfocus(&mut self, handle: ClientHandle, setfocus: bool) {
         let client_win = self.clients.get(&handle).map(|c| c.win);
         if let Some(win) = client_win {
             self.grab_buttons(handle, false);
             self.xwrapper.ungrab_keys(win);
-            self.xwrapper.set_window_border_color(win, Colour::WindowInactive);
+            let urgent = self.clients.get(&handle).is_some_and(|c| c.is_urgent);
+            let colour = if urgent { Colour::Urgent } else { Colour::WindowInactive };
+            self.xwrapper.set_window_border_color(win, colour);
             if setfocus {
                 self
                     .xwrapper
                     .set_input_focus(self.root, xlib::RevertToPointerRoot);
-                // XDeleteProperty(dpy, root, netatom[NetActiveWindow]);
+                self.xwrapper.delete_property(self.root, self.xwrapper.atoms.get(Atom::Net(Net::ActiveWindow)));
             }
         }
     }
@@ -368,6 +548,9 @@ impl Gmux {
             lr_padding: 0,
             numlock_mask: 0,
             running: 1,
+            restarting: false,
+            quit_deadline: None,
+            pending_close: HashMap::new(),
             cursor: [CursorId(0); CursorType::Last as usize],
             mons: Vec::new(),
             selected_monitor: 0,
@@ -381,6 +564,9 @@ impl Gmux {
             command_sender,
             command_receiver,
             clients: HashMap::new(),
+            swallowed: HashMap::new(),
+            mru_cycle: None,
+            docks: HashMap::new(),
         };
 
         state.setup();
@@ -415,33 +601,25 @@ impl Gmux {
             mon.tagset = [1, 1];
             mon.mfact = 0.55;
             mon.nmaster = 1;
+            mon.attach = ATTACH_MODE;
+            mon.gaps_enabled = GAPS_ENABLED;
+            mon.set_gaps(GAP_IH, GAP_IV, GAP_OH, GAP_OV);
             mon.show_bar = true;
             mon.top_bar = true;
-            // Calculate window area accounting for the bar height
-            if mon.show_bar {
-                mon.by = if mon.top_bar { 0 } else { self.screen_height - self.bar_height };
-                mon.wy = if mon.top_bar { self.bar_height } else { 0 };
-                mon.wh = self.screen_height - self.bar_height;
-            } else {
-                mon.by = -self.bar_height;
-                mon.wy = 0;
-                mon.wh = self.screen_height;
-            }
-            mon.lt[0] = &LAYOUTS[0];
-            mon.lt[1] = &LAYOUTS[1];
-            mon.lt_symbol = LAYOUTS[0].symbol.to_string();
-            mon.wx = 0;
-            mon.ww = self.screen_width;
+            mon.lt_symbol = mon.layout().symbol().to_string();
+            mon.mw = self.screen_width;
+            mon.mh = self.screen_height;
             let mut wa: xlib::XSetWindowAttributes = std::mem::zeroed();
             wa.override_redirect = 1;
             wa.background_pixmap = xlib::ParentRelative as u64;
             wa.event_mask = xlib::ButtonPressMask | xlib::ExposureMask;
             let valuemask = xlib::CWOverrideRedirect | xlib::CWBackPixmap | xlib::CWEventMask;
+            // Put in place by update_work_area
             let barwin = self.xwrapper.create_window(
                 self.root,
-                mon.wx,
-                mon.by,
-                mon.ww as u32,
+                mon.mx,
+                mon.my,
+                mon.mw as u32,
                 self.bar_height as u32,
                 0,
                 self.xwrapper.default_depth(self.screen),
@@ -454,6 +632,7 @@ impl Gmux {
             self.xwrapper.map_raised(mon.bar_window);
             self.mons.push(mon);
             self.selected_monitor = self.mons.len() - 1;
+            self.update_work_area(self.selected_monitor);
 
             self.cursor[CursorType::Normal as usize] = self.xwrapper.create_font_cursor_as_id(68);
             self.cursor[CursorType::Resize as usize] = self.xwrapper.create_font_cursor_as_id(120);
@@ -473,6 +652,9 @@ impl Gmux {
             self.xwrapper.change_property(self.root, self.xwrapper.atoms.get(Atom::Net(Net::Supported)), xlib::XA_ATOM, 32,
                 xlib::PropModeReplace, self.xwrapper.atoms.net_atom_ptr() as *const c_uchar, Net::Last as i32);
             self.xwrapper.delete_property(self.root, self.xwrapper.atoms.get(Atom::Net(Net::ClientList)));
+            self.xwrapper.set_long_property(self.root, self.xwrapper.atoms.get(Atom::Net(Net::NumberOfDesktops)),
+                xlib::XA_CARDINAL, &[TAGS.len() as c_ulong]);
+            self.xwrapper.set_utf8_strings(self.root, self.xwrapper.atoms.get(Atom::Net(Net::DesktopNames)), &TAGS);
 
             let mut wa: xlib::XSetWindowAttributes = std::mem::zeroed();
             wa.cursor = self.cursor[CursorType::Normal as usize].0;
@@ -535,36 +717,14 @@ impl Gmux {
         };
         
         if let Some((win, mon_idx)) = client_info {
+            self.update_net_wm_state(handle);
             if should_set_fs {
-                // Set _NET_WM_STATE to _NET_WM_STATE_FULLSCREEN
-                let fullscreen_atom = self.xwrapper.atoms.get(Atom::Net(Net::WMFullscreen));
-                self.xwrapper.change_property(
-                    win,
-                    self.xwrapper.atoms.get(Atom::Net(Net::WMState)),
-                    xlib::XA_ATOM,
-                    32,
-                    xlib::PropModeReplace,
-                    &fullscreen_atom as *const xlib::Atom as *const u8,
-                    1,
-                );
-                
                 // Resize to full monitor dimensions
                 let mon_ww = self.mons[mon_idx].ww;
                 self.resize(handle, 0, 0, mon_ww, self.screen_height);
                 self.xwrapper.raise_window(win);
                 
             } else if should_unset_fs {
-                // Clear _NET_WM_STATE
-                self.xwrapper.change_property(
-                    win,
-                    self.xwrapper.atoms.get(Atom::Net(Net::WMState)),
-                    xlib::XA_ATOM,
-                    32,
-                    xlib::PropModeReplace,
-                    std::ptr::null(),
-                    0,
-                );
-                
                 // Get the restored geometry from the client
                 let (x, y, w, h) = if let Some(client) = self.clients.get(&handle) {
                     (client.x, client.y, client.w, client.h)
@@ -578,6 +738,140 @@ impl Gmux {
         }
     }
 
+    pub fn set_sticky(&mut self, handle: ClientHandle, sticky: bool) {
+        let mon_idx = match self.clients.get_mut(&handle) {
+            Some(client) if client.is_sticky != sticky => {
+                client.is_sticky = sticky;
+                client.monitor_idx
+            }
+            _ => return,
+        };
+        self.update_net_wm_state(handle);
+        self.arrange(Some(mon_idx));
+    }
+
+    pub fn net_atom(&self, net: Net) -> xlib::Atom {
+        self.xwrapper.atoms.get(Atom::Net(net))
+    }
+
+    /// Writes the client's flags to its _NET_WM_STATE.
+    pub fn update_net_wm_state(&mut self, handle: ClientHandle) {
+        let client = match self.clients.get(&handle) {
+            Some(c) => c,
+            None => return,
+        };
+        let mut states: Vec<xlib::Atom> = Vec::new();
+        if client.is_fullscreen {
+            states.push(self.xwrapper.atoms.get(Atom::Net(Net::WMFullscreen)));
+        }
+        if client.is_sticky {
+            states.push(self.xwrapper.atoms.get(Atom::Net(Net::WMSticky)));
+        }
+        if client.is_above {
+            states.push(self.xwrapper.atoms.get(Atom::Net(Net::WMAbove)));
+        }
+        if client.is_hidden {
+            states.push(self.xwrapper.atoms.get(Atom::Net(Net::WMHidden)));
+        }
+        if client.is_urgent {
+            states.push(self.xwrapper.atoms.get(Atom::Net(Net::WMDemandsAttention)));
+        }
+        self.xwrapper.change_property(
+            client.win,
+            self.xwrapper.atoms.get(Atom::Net(Net::WMState)),
+            xlib::XA_ATOM,
+            32,
+            xlib::PropModeReplace,
+            states.as_ptr() as *const u8,
+            states.len() as i32,
+        );
+    }
+
+    pub fn set_above(&mut self, handle: ClientHandle, above: bool) {
+        let mon_idx = match self.clients.get_mut(&handle) {
+            Some(client) if client.is_above != above => {
+                client.is_above = above;
+                client.monitor_idx
+            }
+            _ => return,
+        };
+        self.update_net_wm_state(handle);
+        self.restack(mon_idx);
+    }
+
+    /// Iconifies a client or brings it back. Hidden clients stay mapped and are
+    /// parked off-screen like those on other tags.
+    pub fn set_hidden(&mut self, handle: ClientHandle, hidden: bool) {
+        let (win, mon_idx) = match self.clients.get_mut(&handle) {
+            Some(client) if client.is_hidden != hidden => {
+                client.is_hidden = hidden;
+                (client.win, client.monitor_idx)
+            }
+            _ => return,
+        };
+        if hidden {
+            self.xwrapper.set_wm_state(win, ICONIC_STATE);
+        } else {
+            self.xwrapper.set_wm_state(win, NORMAL_STATE);
+            // Windows adopted as iconic by `scan` were never mapped
+            self.xwrapper.map_window(win);
+        }
+        self.update_net_wm_state(handle);
+        self.arrange(Some(mon_idx));
+    }
+
+    /// Flags or clears a client's urgency, keeping the `XUrgencyHint` in its
+    /// WM_HINTS in step like dwm's seturgent.
+    pub fn set_urgent(&mut self, handle: ClientHandle, urgent: bool) {
+        let win = match self.clients.get_mut(&handle) {
+            Some(client) if client.is_urgent != urgent => {
+                client.is_urgent = urgent;
+                client.urgent_since = urgent.then(std::time::Instant::now);
+                client.win
+            }
+            _ => return,
+        };
+        let stale = self.xwrapper.get_wm_hints(win)
+            .filter(|h| (h.flags & xlib::XUrgencyHint != 0) != urgent);
+        if let Some(mut hints) = stale {
+            hints.flags ^= xlib::XUrgencyHint;
+            self.xwrapper.set_wm_hints(win, &mut hints);
+        }
+        let colour = if urgent {
+            Colour::Urgent
+        } else if self.mons.iter().any(|m| m.sel == Some(handle)) {
+            Colour::WindowActive
+        } else {
+            Colour::WindowInactive
+        };
+        self.xwrapper.set_window_border_color(win, colour);
+        self.update_net_wm_state(handle);
+        self.draw_bars();
+    }
+
+    /// Rereads urgency and the input hint from WM_HINTS, like dwm's
+    /// updatewmhints. The focused client has its urgency cleared instead.
+    pub fn update_wm_hints(&mut self, handle: ClientHandle) {
+        let win = match self.clients.get(&handle) {
+            Some(c) => c.win,
+            None => return,
+        };
+        let mut hints = match self.xwrapper.get_wm_hints(win) {
+            Some(h) => h,
+            None => return,
+        };
+        let urgent = hints.flags & xlib::XUrgencyHint != 0;
+        if urgent && self.mons[self.selected_monitor].sel == Some(handle) {
+            hints.flags &= !xlib::XUrgencyHint;
+            self.xwrapper.set_wm_hints(win, &mut hints);
+        } else {
+            self.set_urgent(handle, urgent);
+        }
+        if let Some(c) = self.clients.get_mut(&handle) {
+            c.never_focus = hints.flags & xlib::InputHint != 0 && hints.input == 0;
+        }
+    }
+
     /// Sends a synthetic ConfigureNotify event to a client.
     pub fn send_configure_notify(&mut self, handle: ClientHandle) {
         if let Some(c) = self.clients.get(&handle) {
@@ -610,12 +904,58 @@ impl Gmux {
 
 }
 
-impl Drop for Gmux {
-    fn drop(&mut self) {
+impl Gmux {
+    /// Leaves every window the way the next window manager expects to find it,
+    /// like dwm's cleanup: on-screen, with its own border and WM_STATE, and
+    /// with our root window properties gone.
+    fn cleanup(&mut self) {
+        // Floating everything on all tags brings parked windows back in place
+        for mon in self.mons.iter_mut() {
+            mon.tagset[mon.selected_tags as usize] = !0;
+            mon.lt[mon.selected_lt as usize] = Box::new(Floating);
+        }
+        self.arrange(None);
+        // Hidden clients are parked off-screen on every tag. They stay iconic,
+        // but in their own place for whoever manages them next
+        for c in self.clients.values().filter(|c| c.is_hidden) {
+            self.xwrapper.move_window(c.win, c.x, c.y);
+        }
+
+        // Swallowed terminals are not in `clients`, so release them first. A
+        // restart adopts them again as iconic, otherwise they would stay
+        // unmapped for good
+        for (_, term) in std::mem::take(&mut self.swallowed) {
+            self.xwrapper.unmanage_window(term.win);
+            self.xwrapper.set_border_width(term.win, term.orig_bw.max(0) as u32);
+            if self.restarting {
+                self.xwrapper.set_wm_state(term.win, ICONIC_STATE);
+            } else {
+                self.xwrapper.configure_window(term.win, term.x, term.y, term.w, term.h, term.orig_bw);
+                self.xwrapper.map_window(term.win);
+                self.xwrapper.set_wm_state(term.win, WITHDRAWN_STATE);
+            }
+        }
+
         let client_handles: Vec<ClientHandle> = self.clients.keys().cloned().collect();
         for handle in client_handles {
             self.unmanage(handle, false);
         }
         self.xwrapper.ungrab_key(self.root);
+        self.xwrapper.destroy_window(self.wm_check_window);
+        for net in [
+            Net::Supported, Net::ClientList, Net::ClientListStacking, Net::ActiveWindow, Net::WMCheck,
+            Net::NumberOfDesktops, Net::CurrentDesktop, Net::DesktopNames, Net::Workarea,
+        ] {
+            self.xwrapper.delete_property(self.root, self.xwrapper.atoms.get(Atom::Net(net)));
+        }
+        self.xwrapper.delete_property(self.root, self.xwrapper.atoms.gmux_state);
+        self.xwrapper.set_input_focus(Window(xlib::PointerRoot as u64), xlib::RevertToPointerRoot);
+        self.xwrapper.sync(false);
+    }
+}
+
+impl Drop for Gmux {
+    fn drop(&mut self) {
+        self.cleanup();
     }
 }
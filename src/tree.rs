@@ -0,0 +1,610 @@
+use std::collections::HashMap;
+
+use crate::{ClientHandle, Window};
+use crate::layouts::{Direction, Gaps, Layout, LayoutInput, Rect};
+
+/// How a split container places its children.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitDir {
+    /// Children side by side.
+    Horizontal,
+    /// Children on top of each other.
+    Vertical,
+}
+
+/// Tabbed and stacked containers give every child the whole container and only
+/// show the focused one. gmux draws no title bars, so the two only differ in the
+/// direction that cycles between children: left/right for tabbed, up/down for
+/// stacked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContainerMode {
+    Split,
+    Tabbed,
+    Stacked,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TreeCommand {
+    /// Windows opened next to the focused one are placed in this direction.
+    Split(SplitDir),
+    Focus(Direction),
+    /// Swaps the focused window with its neighbour in a direction.
+    Move(Direction),
+    /// Grows the focused window's share of its split by this fraction.
+    Resize(f32),
+    /// Switches the focused window's container to a mode, or back to a split.
+    ToggleMode(ContainerMode),
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Leaf(ClientHandle),
+    Container(Container),
+}
+
+#[derive(Debug, Clone)]
+struct Child {
+    node: Node,
+    weight: f32,
+}
+
+#[derive(Debug, Clone)]
+struct Container {
+    split: SplitDir,
+    mode: ContainerMode,
+    children: Vec<Child>,
+    /// Index of the child that was focused last.
+    focus: usize,
+}
+
+impl Container {
+    fn new(split: SplitDir) -> Self {
+        Container { split, mode: ContainerMode::Split, children: Vec::new(), focus: 0 }
+    }
+
+    /// Whether moving in `dir` walks along this container's children.
+    fn follows(&self, dir: Direction) -> bool {
+        let horizontal = matches!(dir, Direction::Left | Direction::Right);
+        match self.mode {
+            ContainerMode::Split => horizontal == (self.split == SplitDir::Horizontal),
+            ContainerMode::Tabbed => horizontal,
+            ContainerMode::Stacked => !horizontal,
+        }
+    }
+
+    fn path_to(&self, handle: ClientHandle) -> Option<Vec<usize>> {
+        for (i, child) in self.children.iter().enumerate() {
+            match &child.node {
+                Node::Leaf(h) if *h == handle => return Some(vec![i]),
+                Node::Container(c) => {
+                    if let Some(mut path) = c.path_to(handle) {
+                        path.insert(0, i);
+                        return Some(path);
+                    }
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    /// The container reached by following `path`, which must only pass through containers.
+    fn get_mut(&mut self, path: &[usize]) -> &mut Container {
+        match path.split_first() {
+            None => self,
+            Some((&i, rest)) => match &mut self.children[i].node {
+                Node::Container(c) => c.get_mut(rest),
+                Node::Leaf(_) => unreachable!("tree path runs through a leaf"),
+            },
+        }
+    }
+
+    fn get(&self, path: &[usize]) -> &Container {
+        match path.split_first() {
+            None => self,
+            Some((&i, rest)) => match &self.children[i].node {
+                Node::Container(c) => c.get(rest),
+                Node::Leaf(_) => unreachable!("tree path runs through a leaf"),
+            },
+        }
+    }
+
+    /// Follows the focus of every container down to a window.
+    fn focused_leaf(&self) -> Option<ClientHandle> {
+        match &self.children.get(self.focus)?.node {
+            Node::Leaf(h) => Some(*h),
+            Node::Container(c) => c.focused_leaf(),
+        }
+    }
+
+    fn contains(&self, handle: ClientHandle) -> bool {
+        self.path_to(handle).is_some()
+    }
+
+    /// Space separated tokens: `( <split> <mode> <focus>`, then a weight and a
+    /// node per child, then `)`. Windows are their ids.
+    fn save(&self, out: &mut Vec<String>) {
+        let split = match self.split {
+            SplitDir::Horizontal => "h",
+            SplitDir::Vertical => "v",
+        };
+        let mode = match self.mode {
+            ContainerMode::Split => "s",
+            ContainerMode::Tabbed => "t",
+            ContainerMode::Stacked => "k",
+        };
+        out.extend(["(".to_string(), split.to_string(), mode.to_string(), self.focus.to_string()]);
+        for child in &self.children {
+            out.push(child.weight.to_string());
+            match &child.node {
+                Node::Leaf(h) => out.push(h.window().0.to_string()),
+                Node::Container(c) => c.save(out),
+            }
+        }
+        out.push(")".to_string());
+    }
+
+    /// Reads back what `save` wrote, the opening `(` already taken.
+    fn load<'a>(tokens: &mut impl Iterator<Item = &'a str>) -> Option<Self> {
+        let split = match tokens.next()? {
+            "h" => SplitDir::Horizontal,
+            "v" => SplitDir::Vertical,
+            _ => return None,
+        };
+        let mode = match tokens.next()? {
+            "s" => ContainerMode::Split,
+            "t" => ContainerMode::Tabbed,
+            "k" => ContainerMode::Stacked,
+            _ => return None,
+        };
+        let mut container = Container { split, mode, children: Vec::new(), focus: tokens.next()?.parse().ok()? };
+        loop {
+            let weight = match tokens.next()? {
+                ")" => break,
+                w => w.parse().ok()?,
+            };
+            let node = match tokens.next()? {
+                "(" => Node::Container(Container::load(tokens)?),
+                id => Node::Leaf(ClientHandle::from(Window(id.parse().ok()?))),
+            };
+            container.children.push(Child { node, weight });
+        }
+        container.focus = container.focus.min(container.children.len().saturating_sub(1));
+        Some(container)
+    }
+
+    /// Drops windows that `keep` rejects along with any containers left empty.
+    fn retain(&mut self, keep: &dyn Fn(ClientHandle) -> bool) {
+        self.children.retain_mut(|child| match &mut child.node {
+            Node::Leaf(h) => keep(*h),
+            Node::Container(c) => {
+                c.retain(keep);
+                !c.children.is_empty()
+            }
+        });
+        self.focus = self.focus.min(self.children.len().saturating_sub(1));
+    }
+
+    fn place(&self, r: Rect, gaps: Gaps, out: &mut HashMap<ClientHandle, Rect>, shown: &mut Vec<ClientHandle>, visible: bool) {
+        let n = self.children.len() as i32;
+        if n == 0 {
+            return;
+        }
+        let total: f32 = self.children.iter().map(|c| c.weight).sum();
+        let (gap, mut rest) = match self.split {
+            SplitDir::Horizontal => (gaps.iv, r.w - gaps.iv * (n - 1)),
+            SplitDir::Vertical => (gaps.ih, r.h - gaps.ih * (n - 1)),
+        };
+        let mut facts = total;
+        let mut offset = 0;
+
+        for (i, child) in self.children.iter().enumerate() {
+            let cell = if self.mode == ContainerMode::Split {
+                let size = (rest as f32 * (child.weight / facts)) as i32;
+                facts -= child.weight;
+                rest -= size;
+                let cell = match self.split {
+                    SplitDir::Horizontal => Rect::new(r.x + offset, r.y, size, r.h),
+                    SplitDir::Vertical => Rect::new(r.x, r.y + offset, r.w, size),
+                };
+                offset += size + gap;
+                cell
+            } else {
+                r
+            };
+            let child_visible = visible && (self.mode == ContainerMode::Split || i == self.focus);
+            match &child.node {
+                Node::Leaf(h) => {
+                    out.insert(*h, cell);
+                    if child_visible {
+                        shown.push(*h);
+                    }
+                }
+                Node::Container(c) => c.place(cell, gaps, out, shown, child_visible),
+            }
+        }
+    }
+}
+
+/// The windows of one tagset arranged as a tree of containers.
+#[derive(Debug, Clone)]
+struct Tree {
+    root: Container,
+}
+
+impl Default for Tree {
+    fn default() -> Self {
+        Tree { root: Container::new(SplitDir::Horizontal) }
+    }
+}
+
+impl Tree {
+    /// Marks `handle` as focused in every container above it.
+    fn set_focus(&mut self, handle: ClientHandle) {
+        if let Some(path) = self.root.path_to(handle) {
+            for depth in 0..path.len() {
+                self.root.get_mut(&path[..depth]).focus = path[depth];
+            }
+        }
+    }
+
+    /// Adds a window right after `anchor`, or at the end of the root container.
+    fn insert(&mut self, handle: ClientHandle, anchor: Option<ClientHandle>) {
+        let leaf = Child { node: Node::Leaf(handle), weight: 1.0 };
+        match anchor.and_then(|a| self.root.path_to(a)) {
+            Some(path) => {
+                let (i, parent) = path.split_last().unwrap();
+                self.root.get_mut(parent).children.insert(i + 1, leaf);
+            }
+            None => self.root.children.push(leaf),
+        }
+    }
+
+    /// Finds the window next to `handle` in a direction, i3 style: walk up to the
+    /// first container that lays out along `dir` and has a sibling on that side,
+    /// then descend into the sibling along the focus.
+    fn neighbour(&self, handle: ClientHandle, dir: Direction) -> Option<ClientHandle> {
+        let path = self.root.path_to(handle)?;
+        let forward = matches!(dir, Direction::Right | Direction::Down);
+        for depth in (0..path.len()).rev() {
+            let container = self.root.get(&path[..depth]);
+            if !container.follows(dir) {
+                continue;
+            }
+            let i = path[depth];
+            let target = if forward { i + 1 } else { i.wrapping_sub(1) };
+            if let Some(child) = container.children.get(target) {
+                return match &child.node {
+                    Node::Leaf(h) => Some(*h),
+                    Node::Container(c) => c.focused_leaf(),
+                };
+            }
+        }
+        None
+    }
+
+    fn swap(&mut self, a: ClientHandle, b: ClientHandle) {
+        if let (Some(pa), Some(pb)) = (self.root.path_to(a), self.root.path_to(b)) {
+            let (ia, parent_a) = pa.split_last().unwrap();
+            self.root.get_mut(parent_a).children[*ia].node = Node::Leaf(b);
+            let (ib, parent_b) = pb.split_last().unwrap();
+            self.root.get_mut(parent_b).children[*ib].node = Node::Leaf(a);
+            self.set_focus(a);
+        }
+    }
+
+    fn split(&mut self, handle: ClientHandle, dir: SplitDir) {
+        let Some(path) = self.root.path_to(handle) else { return };
+        let (i, parent_path) = path.split_last().unwrap();
+        let parent = self.root.get_mut(parent_path);
+        if parent.children.len() == 1 {
+            // A container that only holds this window can simply change direction
+            parent.split = dir;
+            parent.mode = ContainerMode::Split;
+            return;
+        }
+        let child = &mut parent.children[*i];
+        let mut container = Container::new(dir);
+        let leaf = std::mem::replace(&mut child.node, Node::Leaf(handle));
+        container.children.push(Child { node: leaf, weight: 1.0 });
+        child.node = Node::Container(container);
+    }
+
+    fn resize(&mut self, handle: ClientHandle, delta: f32) {
+        let Some(path) = self.root.path_to(handle) else { return };
+        // Resize within the closest split that has something to take space from
+        for depth in (0..path.len()).rev() {
+            let container = self.root.get_mut(&path[..depth]);
+            let n = container.children.len();
+            if container.mode != ContainerMode::Split || n < 2 {
+                continue;
+            }
+            let total: f32 = container.children.iter().map(|c| c.weight).sum();
+            let i = path[depth];
+            let share = (container.children[i].weight / total + delta).clamp(0.1, 0.9);
+            let others = total - container.children[i].weight;
+            for (j, child) in container.children.iter_mut().enumerate() {
+                child.weight = if j == i {
+                    share
+                } else {
+                    child.weight / others * (1.0 - share)
+                };
+            }
+            return;
+        }
+    }
+
+    fn toggle_mode(&mut self, handle: ClientHandle, mode: ContainerMode) {
+        let Some(path) = self.root.path_to(handle) else { return };
+        let parent = self.root.get_mut(&path[..path.len() - 1]);
+        parent.mode = if parent.mode == mode { ContainerMode::Split } else { mode };
+    }
+}
+
+/// Manual tiling in the style of i3. Every tagset keeps its own tree of split,
+/// tabbed and stacked containers. The tree is brought in line with the tiled
+/// clients on every arrange, which `manage` and `unmanage` both trigger: new
+/// windows open next to the focused one and closed windows are pruned along
+/// with the containers they leave empty.
+#[derive(Debug, Clone, Default)]
+pub struct TreeLayout {
+    trees: HashMap<u32, Tree>,
+    /// Windows of the last arrangement that are not hidden behind a tab.
+    shown: Vec<ClientHandle>,
+}
+
+impl TreeLayout {
+    /// Applies a command to the tree of `tagset`, relative to the focused window.
+    /// Returns the window that should be focused afterwards, if that changes.
+    pub fn command(&mut self, tagset: u32, focused: ClientHandle, cmd: TreeCommand) -> Option<ClientHandle> {
+        let tree = self.trees.get_mut(&tagset)?;
+        if !tree.root.contains(focused) {
+            return None;
+        }
+        match cmd {
+            TreeCommand::Split(dir) => tree.split(focused, dir),
+            TreeCommand::Focus(dir) => {
+                let target = tree.neighbour(focused, dir)?;
+                tree.set_focus(target);
+                return Some(target);
+            }
+            TreeCommand::Move(dir) => {
+                let target = tree.neighbour(focused, dir)?;
+                tree.swap(focused, target);
+            }
+            TreeCommand::Resize(delta) => tree.resize(focused, delta),
+            TreeCommand::ToggleMode(mode) => tree.toggle_mode(focused, mode),
+        }
+        None
+    }
+}
+
+impl Layout for TreeLayout {
+    fn name(&self) -> &'static str {
+        "tree"
+    }
+
+    fn symbol(&self) -> &'static str {
+        "[T]"
+    }
+
+    fn arrange(&mut self, input: &LayoutInput) -> Vec<Rect> {
+        let tree = self.trees.entry(input.tagset).or_default();
+
+        tree.root.retain(&|h| input.clients.iter().any(|c| c.handle == h));
+        let mut anchor = input.focused
+            .filter(|h| tree.root.contains(*h))
+            .or_else(|| tree.root.focused_leaf());
+        for c in &input.clients {
+            if !tree.root.contains(c.handle) {
+                tree.insert(c.handle, anchor);
+                anchor = Some(c.handle);
+            }
+        }
+        if let Some(h) = input.focused {
+            tree.set_focus(h);
+        }
+
+        let Gaps { oh, ov, .. } = input.gaps;
+        let a = input.area;
+        let area = Rect::new(a.x + ov, a.y + oh, a.w - 2 * ov, a.h - 2 * oh);
+        let mut cells = HashMap::new();
+        self.shown.clear();
+        tree.root.place(area, input.gaps, &mut cells, &mut self.shown, true);
+
+        input.clients.iter()
+            .map(|c| {
+                let r = cells[&c.handle];
+                Rect::new(r.x, r.y, r.w - 2 * c.bw, r.h - 2 * c.bw)
+            })
+            .collect()
+    }
+
+    fn raised(&self) -> &[ClientHandle] {
+        &self.shown
+    }
+
+    /// Every tree as its tagset followed by its root container.
+    fn save_state(&self) -> Option<String> {
+        let mut out = Vec::new();
+        for (tagset, tree) in &self.trees {
+            out.push(tagset.to_string());
+            tree.root.save(&mut out);
+        }
+        Some(out.join(" "))
+    }
+
+    fn restore_state(&mut self, state: &str) {
+        let mut tokens = state.split(' ').filter(|t| !t.is_empty());
+        while let Some(tagset) = tokens.next() {
+            let tree = tagset.parse().ok()
+                .filter(|_| tokens.next() == Some("("))
+                .and_then(|tagset: u32| Some((tagset, Container::load(&mut tokens)?)));
+            match tree {
+                Some((tagset, root)) => {
+                    self.trees.insert(tagset, Tree { root });
+                }
+                None => {
+                    log::warn!("Ignoring malformed tree layout state");
+                    return;
+                }
+            }
+        }
+    }
+
+    fn replace_client(&mut self, old: ClientHandle, new: ClientHandle) {
+        for tree in self.trees.values_mut() {
+            if let Some(path) = tree.root.path_to(old) {
+                let (i, parent) = path.split_last().unwrap();
+                tree.root.get_mut(parent).children[*i].node = Node::Leaf(new);
+            }
+        }
+        for h in self.shown.iter_mut().filter(|h| **h == old) {
+            *h = new;
+        }
+    }
+
+    fn as_tree(&mut self) -> Option<&mut TreeLayout> {
+        Some(self)
+    }
+
+    fn box_clone(&self) -> Box<dyn Layout> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layouts::LayoutClient;
+
+    fn handle(id: u64) -> ClientHandle {
+        ClientHandle::from(crate::Window(id))
+    }
+
+    fn input(ids: &[u64], focused: u64) -> LayoutInput {
+        LayoutInput {
+            area: Rect::new(0, 0, 1000, 800),
+            clients: ids.iter()
+                .map(|&id| LayoutClient {
+                    handle: handle(id),
+                    bw: 0,
+                    cfact: 1.0,
+                    min_width: 0,
+                    min_height: 0,
+                })
+                .collect(),
+            visible: ids.len(),
+            focused: Some(handle(focused)),
+            tagset: 1,
+            mfact: 0.5,
+            nmaster: 1,
+            gaps: Gaps::default(),
+            min_cell: 20,
+        }
+    }
+
+    #[test]
+    fn test_new_windows_open_next_to_focus() {
+        let mut tree = TreeLayout::default();
+        tree.arrange(&input(&[1], 1));
+        tree.arrange(&input(&[2, 1], 1));
+        let rects = tree.arrange(&input(&[3, 2, 1], 1));
+        // Window order is 1, 3, 2 from left to right
+        assert_eq!(rects, vec![
+            Rect::new(333, 0, 333, 800),
+            Rect::new(666, 0, 334, 800),
+            Rect::new(0, 0, 333, 800),
+        ]);
+
+        // Closing a window hands its space back
+        let rects = tree.arrange(&input(&[2, 1], 1));
+        assert_eq!(rects, vec![Rect::new(500, 0, 500, 800), Rect::new(0, 0, 500, 800)]);
+    }
+
+    #[test]
+    fn test_split_focus_and_move() {
+        let mut tree = TreeLayout::default();
+        tree.arrange(&input(&[1], 1));
+        tree.arrange(&input(&[2, 1], 1));
+        tree.command(1, handle(1), TreeCommand::Split(SplitDir::Vertical));
+        let rects = tree.arrange(&input(&[3, 2, 1], 1));
+        assert_eq!(rects, vec![
+            Rect::new(0, 400, 500, 400),
+            Rect::new(500, 0, 500, 800),
+            Rect::new(0, 0, 500, 400),
+        ]);
+
+        assert_eq!(tree.command(1, handle(1), TreeCommand::Focus(Direction::Down)), Some(handle(3)));
+        assert_eq!(tree.command(1, handle(3), TreeCommand::Focus(Direction::Right)), Some(handle(2)));
+        // Coming back from the right lands on the window focused last
+        assert_eq!(tree.command(1, handle(2), TreeCommand::Focus(Direction::Left)), Some(handle(3)));
+        assert_eq!(tree.command(1, handle(3), TreeCommand::Focus(Direction::Down)), None);
+
+        tree.command(1, handle(3), TreeCommand::Move(Direction::Right));
+        let rects = tree.arrange(&input(&[3, 2, 1], 3));
+        assert_eq!(rects[0], Rect::new(500, 0, 500, 800));
+        assert_eq!(rects[1], Rect::new(0, 400, 500, 400));
+    }
+
+    #[test]
+    fn test_resize_and_tabbed() {
+        let mut tree = TreeLayout::default();
+        tree.arrange(&input(&[1], 1));
+        tree.arrange(&input(&[2, 1], 1));
+        tree.command(1, handle(1), TreeCommand::Resize(0.25));
+        let rects = tree.arrange(&input(&[2, 1], 1));
+        assert_eq!(rects, vec![Rect::new(750, 0, 250, 800), Rect::new(0, 0, 750, 800)]);
+
+        tree.command(1, handle(1), TreeCommand::ToggleMode(ContainerMode::Tabbed));
+        let rects = tree.arrange(&input(&[2, 1], 1));
+        assert_eq!(rects, vec![Rect::new(0, 0, 1000, 800), Rect::new(0, 0, 1000, 800)]);
+        assert_eq!(tree.raised(), &[handle(1)]);
+        assert_eq!(tree.command(1, handle(1), TreeCommand::Focus(Direction::Right)), Some(handle(2)));
+        assert_eq!(tree.command(1, handle(1), TreeCommand::Focus(Direction::Down)), None);
+    }
+
+    #[test]
+    fn test_replace_client_keeps_position() {
+        let mut tree = TreeLayout::default();
+        tree.arrange(&input(&[1], 1));
+        tree.arrange(&input(&[2, 1], 1));
+        tree.replace_client(handle(1), handle(3));
+        let rects = tree.arrange(&input(&[2, 3], 3));
+        assert_eq!(rects, vec![Rect::new(500, 0, 500, 800), Rect::new(0, 0, 500, 800)]);
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        let mut tree = TreeLayout::default();
+        tree.arrange(&input(&[1], 1));
+        tree.arrange(&input(&[2, 1], 1));
+        tree.command(1, handle(1), TreeCommand::Split(SplitDir::Vertical));
+        tree.arrange(&input(&[3, 2, 1], 1));
+        tree.command(1, handle(3), TreeCommand::Resize(0.1));
+        tree.command(1, handle(3), TreeCommand::ToggleMode(ContainerMode::Stacked));
+        let mut other = input(&[4], 4);
+        other.tagset = 2;
+        tree.arrange(&other);
+
+        let mut restored = TreeLayout::default();
+        restored.restore_state(&tree.save_state().unwrap());
+        assert_eq!(restored.arrange(&input(&[3, 2, 1], 3)), tree.arrange(&input(&[3, 2, 1], 3)));
+        assert_eq!(restored.raised(), tree.raised());
+        assert_eq!(restored.arrange(&other), tree.arrange(&other));
+
+        let mut garbage = TreeLayout::default();
+        garbage.restore_state("1 ( h s 0 1.0 5");
+        assert!(garbage.trees.is_empty());
+    }
+
+    #[test]
+    fn test_tagsets_have_separate_trees() {
+        let mut tree = TreeLayout::default();
+        tree.arrange(&input(&[1, 2], 1));
+        let mut other = input(&[3], 3);
+        other.tagset = 2;
+        assert_eq!(tree.arrange(&other), vec![Rect::new(0, 0, 1000, 800)]);
+        assert_eq!(tree.arrange(&input(&[1, 2], 1)).len(), 2);
+    }
+}
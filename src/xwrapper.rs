@@ -1,6 +1,8 @@
 use x11::xft::XftDraw;
+use std::cell::RefCell;
+use std::collections::HashSet;
 use std::ffi::CString;
-use std::os::raw::{c_int, c_uint};
+use std::os::raw::{c_int, c_long, c_uchar, c_uint, c_ulong};
 use std::ptr::null_mut;
 use x11::{keysym, xft, xlib};
 use crate::colour::{ALL_COLOURS, Colour};
@@ -22,6 +24,11 @@ pub const X_GRAB_BUTTON: u8 = 28;
 pub const X_GRAB_KEY: u8 = 33;
 pub const X_COPY_AREA: u8 = 62;
 
+// WM_STATE values, from <X11/Xutil.h>
+pub const WITHDRAWN_STATE: i32 = 0;
+pub const NORMAL_STATE: i32 = 1;
+pub const ICONIC_STATE: i32 = 3;
+
 static mut X_ERROR_OCCURRED: bool = false;
 
 #[allow(unused_variables)]
@@ -66,6 +73,7 @@ unsafe extern "C" fn x_error(_dpy: *mut xlib::Display, ee: *mut xlib::XErrorEven
 }
 
 use fontconfig::{self};
+use fontconfig_sys as fc;
 
 #[derive(PartialEq, Copy, Clone)]
 pub enum Net {
@@ -74,10 +82,27 @@ pub enum Net {
     WMState,
     WMCheck,
     WMFullscreen,
+    WMSticky,
+    WMAbove,
+    WMHidden,
+    WMDemandsAttention,
+    CloseWindow,
+    MoveResizeWindow,
+    WMMoveResize,
+    WMPid,
     ActiveWindow,
     WMWindowType,
     WMWindowTypeDialog,
+    WMWindowTypeDock,
+    WMStrut,
+    WMStrutPartial,
     ClientList,
+    ClientListStacking,
+    NumberOfDesktops,
+    CurrentDesktop,
+    DesktopNames,
+    WMDesktop,
+    Workarea,
     Last,
 }
 #[derive(PartialEq, Copy, Clone)]
@@ -98,6 +123,9 @@ pub struct Font {
     pub dpy: *mut xlib::Display,
     pub h: c_uint,
     pub xfont: *mut xft::XftFont,
+    /// The pattern parsed from the configured font name, which fallbacks are
+    /// matched from. Null for fallback fonts.
+    pub pattern: *mut fc::FcPattern,
 }
 
 impl Drop for Font {
@@ -106,6 +134,9 @@ impl Drop for Font {
             if !self.xfont.is_null() {
                 xft::XftFontClose(self.dpy, self.xfont);
             }
+            if !self.pattern.is_null() {
+                fc::FcPatternDestroy(self.pattern);
+            }
         }
     }
 }
@@ -140,6 +171,10 @@ pub struct XWrapper {
     gc: xlib::GC,
     xftdraw: *mut XftDraw,
     pub fonts: Vec<Font>,
+    /// Fonts found through fontconfig for glyphs missing from `fonts`.
+    fallback_fonts: RefCell<Vec<Font>>,
+    /// Characters no font has, so fontconfig is not asked about them again.
+    missing_glyphs: RefCell<HashSet<char>>,
     colors: [Color; ALL_COLOURS.len()],
     pub atoms: Atoms,
 }
@@ -183,6 +218,8 @@ impl XWrapper {
                 gc,
                 xftdraw,
                 fonts: Vec::new(),
+                fallback_fonts: RefCell::new(Vec::new()),
+                missing_glyphs: RefCell::new(HashSet::new()),
                 colors: [std::mem::zeroed(); ALL_COLOURS.len()],
                 atoms,
             };
@@ -254,11 +291,17 @@ impl XWrapper {
                 return false;
             }
 
+            // The font's own pattern is already matched to one file, which
+            // fontconfig ranks above the charset, so keep the requested one
+            // around for finding fallbacks like dwm's drw does
+            let pattern = fc::FcNameParse(cstr.as_ptr() as *const fc::FcChar8);
+
             let h = ((*xfont).ascent + (*xfont).descent) as c_uint;
             let fnt = Font {
                 dpy: self.dpy,
                 h,
                 xfont,
+                pattern,
             };
             self.fonts.push(fnt);
             true
@@ -289,49 +332,104 @@ impl XWrapper {
         }
     }
 
+    /// Draws text, taking each character from the first font that has it like
+    /// dwm's drw_text.
     pub fn text(&mut self, color: Colour, tl: IVec2, wh: IVec2, lpad: u32, text: &str) {
         if self.fonts.is_empty() || text.is_empty() {
             return;
         }
-    
-        unsafe {
-            let clr = &mut self.colors[color as usize];
-            let usedfont = &self.fonts[0];
-    
-            let x = tl.x + lpad as i32;
-    
-            let font_height = (*usedfont.xfont).ascent + (*usedfont.xfont).descent;
-            let y = tl.y + (wh.y - font_height as i32) / 2 + (*usedfont.xfont).ascent as i32;
-    
-            xft::XftDrawStringUtf8(
-                self.xftdraw,
-                clr,
-                usedfont.xfont,
-                x,
-                y,
-                text.as_ptr() as *const u8,
-                text.len() as i32,
-            );
+        let runs = split_runs(text, |c| self.font_for(c));
+        let clr = &self.colors[color as usize];
+        let mut x = tl.x + lpad as i32;
+        for (xfont, run) in runs {
+            unsafe {
+                let font_height = (*xfont).ascent + (*xfont).descent;
+                let y = tl.y + (wh.y - font_height) / 2 + (*xfont).ascent;
+                xft::XftDrawStringUtf8(self.xftdraw, clr, xfont, x, y, run.as_ptr(), run.len() as i32);
+            }
+            x += self.run_width(xfont, run) as i32;
         }
     }
+
+    /// Width of `text` as drawn by `text`, fallback fonts included.
     pub fn text_width(&self, text: &str) -> u32 {
         if self.fonts.is_empty() {
             return 0;
         }
+        split_runs(text, |c| self.font_for(c)).into_iter()
+            .map(|(xfont, run)| self.run_width(xfont, run))
+            .sum()
+    }
+
+    fn run_width(&self, xfont: *mut xft::XftFont, run: &str) -> u32 {
         unsafe {
             let mut ext = std::mem::zeroed();
-            let font = &self.fonts[0];
-            xft::XftTextExtentsUtf8(
-                self.dpy,
-                font.xfont,
-                text.as_ptr() as *const u8,
-                text.len() as i32,
-                &mut ext,
-            );
+            xft::XftTextExtentsUtf8(self.dpy, xfont, run.as_ptr(), run.len() as i32, &mut ext);
             ext.xOff as u32
         }
     }
 
+    /// The configured font or cached fallback that has `c`, looking for a new
+    /// fallback through fontconfig if none does. Characters nothing covers use
+    /// the primary font.
+    fn font_for(&self, c: char) -> *mut xft::XftFont {
+        let has = |f: &Font| unsafe { xft::XftCharExists(self.dpy, f.xfont, c as c_uint) != 0 };
+        if let Some(f) = self.fonts.iter().find(|f| has(f)) {
+            return f.xfont;
+        }
+        if let Some(f) = self.fallback_fonts.borrow().iter().find(|f| has(f)) {
+            return f.xfont;
+        }
+        if !self.missing_glyphs.borrow().contains(&c) {
+            match self.match_font(c) {
+                Some(f) => {
+                    let xfont = f.xfont;
+                    self.fallback_fonts.borrow_mut().push(f);
+                    return xfont;
+                }
+                None => {
+                    self.missing_glyphs.borrow_mut().insert(c);
+                }
+            }
+        }
+        self.fonts[0].xfont
+    }
+
+    /// Asks fontconfig for a font like the primary one that has `c`.
+    fn match_font(&self, c: char) -> Option<Font> {
+        let base = self.fonts[0].pattern;
+        if base.is_null() {
+            return None;
+        }
+        unsafe {
+            let pattern = fallback_pattern(base, c);
+            let mut result = xft::FcResult::NoMatch;
+            let matched = xft::XftFontMatch(self.dpy, self.screen, pattern as *const xft::FcPattern, &mut result);
+            fc::FcPatternDestroy(pattern);
+            if matched.is_null() {
+                return None;
+            }
+
+            // The font owns the matched pattern once it is open
+            let xfont = xft::XftFontOpenPattern(self.dpy, matched);
+            if xfont.is_null() {
+                fc::FcPatternDestroy(matched as *mut fc::FcPattern);
+                return None;
+            }
+            let font = Font {
+                dpy: self.dpy,
+                h: ((*xfont).ascent + (*xfont).descent) as c_uint,
+                xfont,
+                pattern: null_mut(),
+            };
+            if xft::XftCharExists(self.dpy, xfont, c as c_uint) == 0 {
+                return None;
+            }
+            log::info!("Using a fallback font for {:?}", c);
+            Some(font)
+        }
+    }
+
     pub fn map_drawable(&mut self, win: Window, x: i32, y: i32, w: u32, h: u32) {
         unsafe {
             xlib::XCopyArea(self.dpy, self.drawable, win.0, self.gc, x, y, w, h, x, y);
@@ -422,6 +520,14 @@ impl XWrapper {
         }
     }
 
+    pub fn destroy_window(&self, win: Window) {
+        unsafe { xlib::XDestroyWindow(self.dpy, win.0) };
+    }
+
+    pub fn set_border_width(&self, win: Window, border_width: u32) {
+        unsafe { xlib::XSetWindowBorderWidth(self.dpy, win.0, border_width) };
+    }
+
     pub fn change_window_attributes(
         &self,
         win: Window,
@@ -473,6 +579,56 @@ impl XWrapper {
         }
     }
 
+    /// Reads a 32 bit property, such as a list of atoms or cardinals. Empty if it
+    /// is not set or has another type.
+    pub fn get_long_property(&self, win: Window, property: xlib::Atom, type_: xlib::Atom) -> Vec<i64> {
+        unsafe {
+            let mut actual_type: xlib::Atom = 0;
+            let mut actual_format: c_int = 0;
+            let mut nitems: c_ulong = 0;
+            let mut bytes_after: c_ulong = 0;
+            let mut prop: *mut c_uchar = std::ptr::null_mut();
+            let result = xlib::XGetWindowProperty(
+                self.dpy,
+                win.0,
+                property,
+                0,
+                64,
+                0,
+                type_,
+                &mut actual_type,
+                &mut actual_format,
+                &mut nitems,
+                &mut bytes_after,
+                &mut prop,
+            );
+            if result != xlib::Success as i32 || prop.is_null() {
+                return Vec::new();
+            }
+            // 32 bit properties come back as longs
+            let values = if actual_format == 32 {
+                std::slice::from_raw_parts(prop as *const c_long, nitems as usize).to_vec()
+            } else {
+                Vec::new()
+            };
+            xlib::XFree(prop as *mut _);
+            values
+        }
+    }
+
+    /// Replaces a 32 bit property, such as a list of windows or cardinals.
+    pub fn set_long_property(&self, win: Window, property: xlib::Atom, type_: xlib::Atom, data: &[c_ulong]) {
+        self.change_property(win, property, type_, 32, xlib::PropModeReplace, data.as_ptr() as *const u8, data.len() as i32);
+    }
+
+    /// Replaces a property with a list of UTF-8 strings, each null terminated.
+    pub fn set_utf8_strings(&self, win: Window, property: xlib::Atom, strings: &[&str]) {
+        let data: Vec<u8> = strings.iter()
+            .flat_map(|s| s.bytes().chain(std::iter::once(0)))
+            .collect();
+        self.change_property(win, property, self.atoms.utf8_string, 8, xlib::PropModeReplace, data.as_ptr(), data.len() as i32);
+    }
+
     pub fn delete_property(&self, win: Window, property: xlib::Atom) {
         unsafe {
             xlib::XDeleteProperty(self.dpy, win.0, property);
@@ -570,6 +726,20 @@ impl XWrapper {
         }
     }
 
+    /// WM_CLIENT_MACHINE, the host the client runs on.
+    pub fn get_client_machine(&self, win: Window) -> Option<String> {
+        unsafe {
+            let mut text_prop: xlib::XTextProperty = std::mem::zeroed();
+            if xlib::XGetWMClientMachine(self.dpy, win.0, &mut text_prop) == 0 || text_prop.value.is_null() {
+                return None;
+            }
+            let bytes = std::slice::from_raw_parts(text_prop.value, text_prop.nitems as usize);
+            let machine = String::from_utf8_lossy(bytes).trim_end_matches('\0').to_string();
+            xlib::XFree(text_prop.value as *mut _);
+            Some(machine)
+        }
+    }
+
     pub fn get_window_class(&self, win: Window) -> Option<(String, String)> {
         unsafe {
             use std::ffi::CStr;
@@ -603,6 +773,85 @@ impl XWrapper {
         }
     }
 
+    /// Reads the _NET_WM_PID of a window, if the client set one.
+    pub fn get_window_pid(&self, win: Window) -> Option<u32> {
+        unsafe {
+            let mut actual_type: xlib::Atom = 0;
+            let mut actual_format: c_int = 0;
+            let mut nitems: c_ulong = 0;
+            let mut bytes_after: c_ulong = 0;
+            let mut prop: *mut c_uchar = std::ptr::null_mut();
+            let result = xlib::XGetWindowProperty(
+                self.dpy,
+                win.0,
+                self.atoms.get(Atom::Net(Net::WMPid)),
+                0,
+                1,
+                0,
+                xlib::XA_CARDINAL,
+                &mut actual_type,
+                &mut actual_format,
+                &mut nitems,
+                &mut bytes_after,
+                &mut prop,
+            );
+            if result != xlib::Success as i32 || prop.is_null() {
+                return None;
+            }
+            // 32 bit properties come back as longs
+            let pid = if nitems > 0 && actual_format == 32 {
+                Some(*(prop as *const c_ulong) as u32)
+            } else {
+                None
+            };
+            xlib::XFree(prop as *mut _);
+            pid.filter(|&p| p != 0)
+        }
+    }
+
+    /// Reads the ICCCM WM_STATE of a window, e.g. `ICONIC_STATE`.
+    pub fn get_wm_state(&self, win: Window) -> Option<i32> {
+        unsafe {
+            let wm_state = self.atoms.get(Atom::Wm(WM::State));
+            let mut actual_type: xlib::Atom = 0;
+            let mut actual_format: c_int = 0;
+            let mut nitems: c_ulong = 0;
+            let mut bytes_after: c_ulong = 0;
+            let mut prop: *mut c_uchar = std::ptr::null_mut();
+            let result = xlib::XGetWindowProperty(
+                self.dpy,
+                win.0,
+                wm_state,
+                0,
+                2,
+                0,
+                wm_state,
+                &mut actual_type,
+                &mut actual_format,
+                &mut nitems,
+                &mut bytes_after,
+                &mut prop,
+            );
+            if result != xlib::Success as i32 || prop.is_null() {
+                return None;
+            }
+            let state = if nitems > 0 && actual_format == 32 {
+                Some(*(prop as *const c_ulong) as i32)
+            } else {
+                None
+            };
+            xlib::XFree(prop as *mut _);
+            state
+        }
+    }
+
+    /// Sets the ICCCM WM_STATE of a client window.
+    pub fn set_wm_state(&self, win: Window, state: i32) {
+        let data: [c_ulong; 2] = [state as c_ulong, 0]; // no icon window
+        let wm_state = self.atoms.get(Atom::Wm(WM::State));
+        self.change_property(win, wm_state, wm_state, 32, xlib::PropModeReplace, data.as_ptr() as *const u8, 2);
+    }
+
     pub fn get_wm_normal_hints(&self, win: Window) -> Result<xlib::XSizeHints, ()> {
         unsafe {
             let mut hints: xlib::XSizeHints = std::mem::zeroed();
@@ -615,6 +864,24 @@ impl XWrapper {
         }
     }
 
+    pub fn get_wm_hints(&self, win: Window) -> Option<xlib::XWMHints> {
+        unsafe {
+            let ptr = xlib::XGetWMHints(self.dpy, win.0);
+            if ptr.is_null() {
+                return None;
+            }
+            let hints = *ptr;
+            xlib::XFree(ptr as *mut _);
+            Some(hints)
+        }
+    }
+
+    pub fn set_wm_hints(&self, win: Window, hints: &mut xlib::XWMHints) {
+        unsafe {
+            xlib::XSetWMHints(self.dpy, win.0, hints);
+        }
+    }
+
     pub fn query_tree(&self, win: Window) -> Result<(Window, Window, Vec<Window>), ()> {
         unsafe {
             let mut root_return: xlib::Window = 0;
@@ -656,6 +923,10 @@ impl XWrapper {
         unsafe { xlib::XMapWindow(self.dpy, win.0) };
     }
 
+    pub fn unmap_window(&self, win: Window) {
+        unsafe { xlib::XUnmapWindow(self.dpy, win.0) };
+    }
+
     pub fn raise_window(&self, win: Window) {
         unsafe { xlib::XRaiseWindow(self.dpy, win.0) };
     }
@@ -846,6 +1117,27 @@ impl XWrapper {
         unsafe { xlib::XSync(self.dpy, if discard { 1 } else { 0 }) };
     }
 
+    /// Number of events that can be read without blocking.
+    pub fn pending(&self) -> i32 {
+        unsafe { xlib::XPending(self.dpy) }
+    }
+
+    /// Waits up to `timeout` for an event to arrive on the X connection.
+    /// Returns whether one can be read without blocking.
+    pub fn wait_for_event(&self, timeout: std::time::Duration) -> bool {
+        if self.pending() > 0 {
+            return true;
+        }
+        let mut fds = libc::pollfd {
+            fd: unsafe { xlib::XConnectionNumber(self.dpy) },
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let ms = timeout.as_millis().min(c_int::MAX as u128) as c_int;
+        unsafe { libc::poll(&mut fds, 1, ms) };
+        self.pending() > 0
+    }
+
     // MODIFIED: This now includes the events needed for redrawing on wake.
     pub fn next_event(&self) -> Option<Event> {
         let mut ev = unsafe { std::mem::zeroed() };
@@ -853,12 +1145,14 @@ impl XWrapper {
         let event_type = ev.get_type();
         match event_type {
             xlib::KeyPress => Some(Event::KeyPress(unsafe { ev.key })),
+            xlib::KeyRelease => Some(Event::KeyRelease(unsafe { ev.key })),
             xlib::ButtonPress => Some(Event::ButtonPress(unsafe { ev.button })),
             xlib::MotionNotify => Some(Event::MotionNotify(unsafe { ev.motion })),
             xlib::MapRequest => Some(Event::MapRequest(unsafe { ev.map_request })),
             xlib::UnmapNotify => Some(Event::UnmapNotify(unsafe { ev.unmap })),
             xlib::DestroyNotify => Some(Event::DestroyNotify(unsafe { ev.destroy_window })),
             xlib::EnterNotify => Some(Event::EnterNotify(unsafe { ev.crossing })),
+            xlib::FocusIn => Some(Event::FocusIn(unsafe { ev.focus_change })),
             xlib::PropertyNotify => Some(Event::PropertyNotify(unsafe { ev.property })),
             // ADDED:
             xlib::ConfigureNotify => Some(Event::ConfigureNotify(unsafe { ev.configure })),
@@ -897,6 +1191,11 @@ impl XWrapper {
     }
 
     pub fn query_pointer_position(&self) -> Option<(i32, i32)> {
+        self.query_pointer().map(|(x, y, _)| (x, y))
+    }
+
+    /// Pointer position on the root window and the button and modifier mask.
+    pub fn query_pointer(&self) -> Option<(i32, i32, c_uint)> {
         unsafe {
             let mut root_return = 0;
             let mut child_return = 0;
@@ -922,7 +1221,7 @@ impl XWrapper {
             );
 
             if result != 0 {
-                Some((root_x_return, root_y_return))
+                Some((root_x_return, root_y_return, mask_return))
             } else {
                 None
             }
@@ -1035,6 +1334,54 @@ impl XWrapper {
         }
     }
 
+    pub fn grab_pointer(&self, win: Window, cursor: CursorId) -> bool {
+        let mask = xlib::ButtonPressMask | xlib::ButtonReleaseMask | xlib::PointerMotionMask;
+        unsafe {
+            xlib::XGrabPointer(
+                self.dpy,
+                win.0,
+                0,
+                mask as c_uint,
+                xlib::GrabModeAsync,
+                xlib::GrabModeAsync,
+                0,
+                cursor.0,
+                xlib::CurrentTime,
+            ) == xlib::GrabSuccess
+        }
+    }
+
+    pub fn ungrab_pointer(&self) {
+        unsafe {
+            xlib::XUngrabPointer(self.dpy, xlib::CurrentTime);
+        }
+    }
+
+    /// Blocks until an event matching `mask` arrives, leaving others queued.
+    pub fn mask_event(&self, mask: i64) -> xlib::XEvent {
+        unsafe {
+            let mut ev: xlib::XEvent = std::mem::zeroed();
+            xlib::XMaskEvent(self.dpy, mask, &mut ev);
+            ev
+        }
+    }
+
+    /// Blocks until an event of one of `types` arrives, leaving others queued.
+    /// Unlike `mask_event` this can wait for client messages.
+    pub fn next_event_of(&self, types: &[c_int]) -> xlib::XEvent {
+        unsafe extern "C" fn matches(_: *mut xlib::Display, ev: *mut xlib::XEvent, arg: xlib::XPointer) -> c_int {
+            unsafe {
+                let types = &*(arg as *const &[c_int]);
+                types.contains(&(*ev).get_type()) as c_int
+            }
+        }
+        unsafe {
+            let mut ev: xlib::XEvent = std::mem::zeroed();
+            xlib::XIfEvent(self.dpy, &mut ev, Some(matches), &types as *const &[c_int] as xlib::XPointer);
+            ev
+        }
+    }
+
     pub fn ungrab_keyboard(&self) {
         unsafe {
             xlib::XUngrabKeyboard(self.dpy, xlib::CurrentTime);
@@ -1045,6 +1392,9 @@ impl XWrapper {
 
 impl Drop for XWrapper {
     fn drop(&mut self) {
+        // Fonts need the display to close
+        self.fallback_fonts.get_mut().clear();
+        self.fonts.clear();
         unsafe {
             if !self.xftdraw.is_null() {
                 xft::XftDrawDestroy(self.xftdraw);
@@ -1065,12 +1415,14 @@ pub enum XError {
 #[derive(Debug)]
 pub enum Event {
     KeyPress(xlib::XKeyEvent),
+    KeyRelease(xlib::XKeyEvent),
     ButtonPress(xlib::XButtonPressedEvent),
     MotionNotify(xlib::XMotionEvent),
     MapRequest(xlib::XMapRequestEvent),
     UnmapNotify(xlib::XUnmapEvent),
     DestroyNotify(xlib::XDestroyWindowEvent),
     EnterNotify(xlib::XCrossingEvent),
+    FocusIn(xlib::XFocusChangeEvent),
     PropertyNotify(xlib::XPropertyEvent),
     ConfigureNotify(xlib::XConfigureEvent),
     ConfigureRequest(xlib::XConfigureRequestEvent),
@@ -1079,9 +1431,52 @@ pub enum Event {
     Unknown,
 }
 
+/// A pattern asking for a font like `base` that has `c`, ready to be matched.
+/// The caller destroys it.
+unsafe fn fallback_pattern(base: *const fc::FcPattern, c: char) -> *mut fc::FcPattern {
+    unsafe {
+        let charset = fc::FcCharSetCreate();
+        fc::FcCharSetAddChar(charset, c as fc::FcChar32);
+        let pattern = fc::FcPatternDuplicate(base);
+        fc::FcPatternAddCharSet(pattern, fc::constants::FC_CHARSET.as_ptr(), charset);
+        fc::FcPatternAddBool(pattern, fc::constants::FC_SCALABLE.as_ptr(), 1);
+        fc::FcConfigSubstitute(null_mut(), pattern, fc::FcMatchPattern);
+        fc::FcDefaultSubstitute(pattern);
+        fc::FcCharSetDestroy(charset);
+        pattern
+    }
+}
+
+/// Splits text into runs of consecutive characters that `font_of` puts in the
+/// same font.
+fn split_runs<F: Copy + PartialEq>(text: &str, mut font_of: impl FnMut(char) -> F) -> Vec<(F, &str)> {
+    let mut runs: Vec<(F, &str)> = Vec::new();
+    let mut start = 0;
+    let mut current = None;
+    for (i, c) in text.char_indices() {
+        let font = font_of(c);
+        match current {
+            Some(f) if f == font => {}
+            Some(f) => {
+                runs.push((f, &text[start..i]));
+                start = i;
+                current = Some(font);
+            }
+            None => current = Some(font),
+        }
+    }
+    if let Some(f) = current {
+        runs.push((f, &text[start..]));
+    }
+    runs
+}
+
 pub struct Atoms {
     wmatom: [xlib::Atom; WM::Last as usize],
     netatom: [xlib::Atom; Net::Last as usize],
+    pub utf8_string: xlib::Atom,
+    /// Root window property gmux publishes its state in.
+    pub gmux_state: xlib::Atom,
 }
 
 impl Atoms {
@@ -1089,6 +1484,8 @@ impl Atoms {
         let mut atoms = Self {
             wmatom: [0; WM::Last as usize],
             netatom: [0; Net::Last as usize],
+            utf8_string: 0,
+            gmux_state: 0,
         };
 
         let intern = |name: &str| -> Result<xlib::Atom, XError> {
@@ -1107,9 +1504,28 @@ impl Atoms {
         atoms.netatom[Net::WMState as usize] = intern("_NET_WM_STATE")?;
         atoms.netatom[Net::WMCheck as usize] = intern("_NET_SUPPORTING_WM_CHECK")?;
         atoms.netatom[Net::WMFullscreen as usize] = intern("_NET_WM_STATE_FULLSCREEN")?;
+        atoms.netatom[Net::WMSticky as usize] = intern("_NET_WM_STATE_STICKY")?;
+        atoms.netatom[Net::WMAbove as usize] = intern("_NET_WM_STATE_ABOVE")?;
+        atoms.netatom[Net::WMHidden as usize] = intern("_NET_WM_STATE_HIDDEN")?;
+        atoms.netatom[Net::WMDemandsAttention as usize] = intern("_NET_WM_STATE_DEMANDS_ATTENTION")?;
+        atoms.netatom[Net::CloseWindow as usize] = intern("_NET_CLOSE_WINDOW")?;
+        atoms.netatom[Net::MoveResizeWindow as usize] = intern("_NET_MOVERESIZE_WINDOW")?;
+        atoms.netatom[Net::WMMoveResize as usize] = intern("_NET_WM_MOVERESIZE")?;
+        atoms.netatom[Net::WMPid as usize] = intern("_NET_WM_PID")?;
         atoms.netatom[Net::WMWindowType as usize] = intern("_NET_WM_WINDOW_TYPE")?;
         atoms.netatom[Net::WMWindowTypeDialog as usize] = intern("_NET_WM_WINDOW_TYPE_DIALOG")?;
+        atoms.netatom[Net::WMWindowTypeDock as usize] = intern("_NET_WM_WINDOW_TYPE_DOCK")?;
+        atoms.netatom[Net::WMStrut as usize] = intern("_NET_WM_STRUT")?;
+        atoms.netatom[Net::WMStrutPartial as usize] = intern("_NET_WM_STRUT_PARTIAL")?;
         atoms.netatom[Net::ClientList as usize] = intern("_NET_CLIENT_LIST")?;
+        atoms.netatom[Net::ClientListStacking as usize] = intern("_NET_CLIENT_LIST_STACKING")?;
+        atoms.netatom[Net::NumberOfDesktops as usize] = intern("_NET_NUMBER_OF_DESKTOPS")?;
+        atoms.netatom[Net::CurrentDesktop as usize] = intern("_NET_CURRENT_DESKTOP")?;
+        atoms.netatom[Net::DesktopNames as usize] = intern("_NET_DESKTOP_NAMES")?;
+        atoms.netatom[Net::WMDesktop as usize] = intern("_NET_WM_DESKTOP")?;
+        atoms.netatom[Net::Workarea as usize] = intern("_NET_WORKAREA")?;
+        atoms.utf8_string = intern("UTF8_STRING")?;
+        atoms.gmux_state = intern("_GMUX_STATE")?;
 
         Ok(atoms)
     }
@@ -1126,3 +1542,58 @@ impl Atoms {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Whether the font fontconfig matches for `pattern` has `c`.
+    unsafe fn match_has_char(pattern: *mut fc::FcPattern, c: char) -> bool {
+        unsafe {
+            let mut result = fc::FcResultMatch;
+            let matched = fc::FcFontMatch(null_mut(), pattern, &mut result);
+            if matched.is_null() {
+                return false;
+            }
+            let mut charset = null_mut();
+            fc::FcPatternGetCharSet(matched, fc::constants::FC_CHARSET.as_ptr(), 0, &mut charset);
+            let has = !charset.is_null() && fc::FcCharSetHasChar(charset, c as fc::FcChar32) != 0;
+            fc::FcPatternDestroy(matched);
+            has
+        }
+    }
+
+    #[test]
+    fn test_fallback_pattern() {
+        // An emoji that monospace fonts such as DejaVu Sans Mono lack
+        let c = '\u{1F600}';
+        unsafe {
+            let wanted = fc::FcPatternCreate();
+            let charset = fc::FcCharSetCreate();
+            fc::FcCharSetAddChar(charset, c as fc::FcChar32);
+            fc::FcPatternAddCharSet(wanted, fc::constants::FC_CHARSET.as_ptr(), charset);
+            let fonts = fc::FcFontList(null_mut(), wanted, null_mut());
+            let installed = !fonts.is_null() && (*fonts).nfont > 0;
+            fc::FcFontSetDestroy(fonts);
+            fc::FcCharSetDestroy(charset);
+            fc::FcPatternDestroy(wanted);
+            // Nothing to fall back to without a font that has it
+            if !installed {
+                return;
+            }
+
+            let base = fc::FcNameParse(c"monospace:size=20".as_ptr() as *const fc::FcChar8);
+            let pattern = fallback_pattern(base, c);
+            assert!(match_has_char(pattern, c));
+            fc::FcPatternDestroy(pattern);
+            fc::FcPatternDestroy(base);
+        }
+    }
+
+    #[test]
+    fn test_split_runs() {
+        let runs = split_runs("ab日本c", |c| c.is_ascii());
+        assert_eq!(runs, vec![(true, "ab"), (false, "日本"), (true, "c")]);
+        assert_eq!(split_runs("abc", |_| 0), vec![(0, "abc")]);
+        assert!(split_runs("", |_| 0).is_empty());
+    }
+}